@@ -0,0 +1,800 @@
+//! The `tmp` command line, kept in the library so its internals need not be public API
+
+use crate::cli::{Cli, Command, ListOrder};
+use crate::config::{self, KindFilter};
+use crate::error::exit;
+use crate::manifest::{ManifestEntry, load_manifest};
+use crate::registry::{self, Registry};
+use crate::trash::Trash;
+use crate::trust::Trust;
+use crate::vars::Replacement;
+use crate::{
+    BatchReport, CreateOptions, DeleteOptions, Kind, Origin, Tmp, TmpError, Vars, config_edit, editor, logging, man,
+    picker, remove_recorded_file,
+};
+use chrono::Local;
+use clap::CommandFactory;
+use eyre::{Context, Result};
+use log::{debug, error, info, warn};
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// Project configs the user trusts to run commands
+fn trust_store() -> Result<Trust> {
+    Ok(Trust::home()?)
+}
+
+fn setup_logging(cli: &Cli) {
+    let file = match &cli.log_file {
+        Some(path) => config::expand_tilde(path).ok(),
+        None => config::data_dir().ok().map(|dir| dir.join("tmp.log")),
+    };
+    logging::init(&logging::LogOptions {
+        file,
+        verbose: cli.verbose,
+        quiet: cli.quiet,
+        format: cli.log_format,
+    });
+
+    info!("Logging initialized");
+}
+
+/// Exit code for a report, from the first recognised error in its chain
+fn exit_code(report: &eyre::Report) -> u8 {
+    report
+        .chain()
+        .find_map(|cause| {
+            if let Some(e) = cause.downcast_ref::<TmpError>() {
+                Some(e.exit_code())
+            } else if cause.is::<serde_yaml::Error>() {
+                Some(exit::CONFIG)
+            } else if cause.is::<std::io::Error>() {
+                Some(exit::IO)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(exit::FAILURE)
+}
+
+/// Run `tmp` with the process arguments
+pub fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(report) => {
+            error!("{report:?}");
+            eprintln!("tmp: {report:#}");
+            ExitCode::from(exit_code(&report))
+        }
+    }
+}
+
+fn run() -> Result<()> {
+    // Answers TAB completion requests from the script `tmp completions` prints, then exits
+    clap_complete::CompleteEnv::with_factory(Cli::command).complete();
+
+    let cli = Cli::try_parse_args(std::env::args_os()).unwrap_or_else(|e| e.exit());
+    setup_logging(&cli);
+
+    info!("Starting tmp application");
+
+    // Creating files works without HOME; only the history is lost
+    let registry = config::data_dir()
+        .map(|dir| Registry::new(dir.join("registry.yml")))
+        .inspect_err(|e| warn!("No registry, files won't be recorded: {e}"))
+        .ok();
+
+    if let Some(command) = &cli.command {
+        return run_command(command, registry.as_ref(), &cli);
+    }
+
+    let app = load_app(&cli, registry.clone())?;
+
+    let kind = match &cli.kind {
+        Some(kind) => kind.clone(),
+        None => choose_kind(&app, &cli)?,
+    };
+    let kind = kind.as_str();
+    let name = cli.names.first().map(String::as_str);
+    let nerf = cli.nerf;
+    let rm = cli.rm;
+    let ttl = cli.ttl.as_deref().map(config::parse_duration).transpose()?;
+    let delete_options = DeleteOptions::default()
+        .with_force(cli.force)
+        .with_trash(cli.trash.then(Trash::home).transpose()?)
+        .with_prune(cli.prune)
+        .with_here(cli.here);
+    let chmod = cli
+        .chmod
+        .as_ref()
+        .map(|s| u32::from_str_radix(s, 8))
+        .transpose()
+        .context("Invalid chmod value, must be octal")?;
+
+    debug!("Processing request - kind: {kind}, name: {name:?}, nerf: {nerf}, rm: {rm}, chmod: {chmod:?}");
+
+    // Validate kind exists
+    if app.find_kind(kind).is_none() {
+        error!("Unknown kind: {kind}");
+        if !cli.quiet {
+            eprintln!("Available kinds:");
+            for kind in app.kinds() {
+                eprintln!("{}", kind.name);
+            }
+        }
+        return Err(TmpError::UnknownKind(kind.to_string()).into());
+    }
+
+    if let (Some(output), Some(name)) = (cli.output.as_deref(), name)
+        && output != "-"
+    {
+        return Err(eyre::eyre!(
+            "Give either NAME ({name}) or --output ({output}), not both"
+        ));
+    }
+
+    let mut vars: Vars = cli.vars.iter().cloned().collect();
+    if cli.stdin {
+        let mut input = String::new();
+        std::io::stdin()
+            .read_to_string(&mut input)
+            .context("Failed to read standard input")?;
+        if !app.find_kind(kind).unwrap().content.contains("{tmp.stdin}") {
+            warn!("Kind {kind} has no {{tmp.stdin}} placeholder, ignoring piped input");
+            if !cli.quiet {
+                eprintln!("tmp: kind '{kind}' has no {{tmp.stdin}} placeholder; piped input was ignored");
+            }
+        }
+        vars.set("stdin", input);
+    }
+
+    let options = CreateOptions::default()
+        .with_ttl(ttl)
+        .with_no_hooks(cli.no_hooks)
+        .with_dry_run(cli.dry_run)
+        .with_parents(cli.parents)
+        .with_here(cli.here)
+        .with_vars(vars);
+
+    if nerf {
+        info!("Nerf mode: printing file content");
+        println!("{}", app.render_kind(kind, None, &options)?);
+    } else if cli.output.as_deref() == Some("-") {
+        info!("Output mode: rendering to stdout");
+        if cli.names.len() > 1 {
+            // Several files: separate them the way `head` does
+            let kind_obj = app.find_kind(kind).unwrap();
+            for (index, name) in cli.names.iter().enumerate() {
+                if index > 0 {
+                    println!();
+                }
+                println!("==> {} <==", kind_obj.filename(name));
+                print!("{}", app.render_kind(kind, Some(name), &options)?);
+            }
+        } else {
+            print!("{}", app.render_kind(kind, name, &options)?);
+        }
+    } else if rm {
+        let filenames = if cli.names.is_empty() {
+            // The kind's newest file, whatever name pattern or directory it was given
+            let last = match &registry {
+                Some(registry) => registry.last_matching(|entry| entry.kind == kind && entry.path.exists())?,
+                None => None,
+            };
+            let entry =
+                last.ok_or_else(|| eyre::eyre!("No file of kind '{kind}' created by tmp is left; give NAME"))?;
+            vec![entry.path.to_string_lossy().to_string()]
+        } else {
+            cli.names.clone()
+        };
+        for filename in &filenames {
+            info!("Remove mode: deleting file: {filename}");
+            app.delete_file(kind, filename, &delete_options)
+                .with_context(|| format!("Failed to delete file: {filename}"))?;
+        }
+    } else {
+        info!("Create mode: creating file");
+        let names: Vec<Option<String>> = match cli.output.as_deref() {
+            Some(output) => vec![Some(output.to_string())],
+            None if cli.names.is_empty() => vec![None],
+            None => cli.names.iter().cloned().map(Some).collect(),
+        };
+        let entries: Vec<ManifestEntry> = names
+            .into_iter()
+            .map(|name| ManifestEntry {
+                kind: kind.to_string(),
+                name,
+                vars: Default::default(),
+            })
+            .collect();
+
+        let report = app.create_batch(&entries, &options, cli.atomic);
+        let created = report_batch(&app, report, &options)?;
+
+        // A kind's `open:` only applies interactively, never inside `$(tmp py)` or a pipe
+        let interactive = std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+        let edit = cli.edit || (interactive && app.find_kind(kind).is_some_and(|k| k.open));
+        if edit && !cli.dry_run {
+            for path in &created {
+                // The path is already printed, so the file stays created whatever the editor does
+                if let Err(e) = app.open_in_editor(kind, path, &options) {
+                    warn!("Failed to open {path:?} in the editor: {e}");
+                    eprintln!("tmp: {e}");
+                }
+            }
+        }
+    }
+
+    info!("tmp application completed successfully");
+    Ok(())
+}
+
+/// Show each `--templatize` replacement with its line and ask whether to apply them
+fn confirm_replacements(content: &str, replacements: &[Replacement], yes: bool) -> Result<bool> {
+    let lines: Vec<&str> = content.lines().collect();
+    for replacement in replacements {
+        let line = lines.get(replacement.line - 1).copied().unwrap_or_default();
+        eprintln!(
+            "{number:>5}: {value} -> {{tmp.{key}}} in {line:?}",
+            number = replacement.line,
+            value = replacement.value,
+            key = replacement.key,
+            line = line.trim()
+        );
+    }
+    if yes {
+        return Ok(true);
+    }
+    if !std::io::stdin().is_terminal() {
+        return Err(eyre::eyre!(
+            "Pass --yes to apply --templatize replacements without a terminal"
+        ));
+    }
+
+    eprint!("Apply these replacements? [y/N] ");
+    std::io::stderr().flush().context("Failed to write to stderr")?;
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .context("Failed to read standard input")?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Ask the user for a kind, with `--picker` or the built-in picker when on a terminal
+fn choose_kind(app: &Tmp, cli: &Cli) -> Result<String> {
+    let kinds: Vec<&Kind> = app.kinds().iter().collect();
+    let choice = if let Some(command) = &cli.picker {
+        picker::pick_with(command, &kinds).with_context(|| format!("Failed to run picker: {command}"))?
+    } else if std::io::stdin().is_terminal() && std::io::stderr().is_terminal() {
+        let options = CreateOptions::default();
+        let preview = |kind: &Kind| {
+            app.render_kind(&kind.name, None, &options)
+                .unwrap_or_else(|e| format!("Can't render {name}: {e}", name = kind.name))
+        };
+        picker::pick(&kinds, &preview).context("Failed to run the kind picker")?
+    } else {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "KIND is required when not on a terminal; run `tmp list` to see the kinds",
+            )
+            .exit()
+    };
+
+    info!("Picked kind: {choice:?}");
+    choice.ok_or_else(|| eyre::eyre!("No kind selected"))
+}
+
+/// The user config's path, or none when the default `~/...` path can't be found without HOME
+fn user_config(cli: &Cli) -> Result<Option<PathBuf>> {
+    match config::expand_tilde(&cli.config) {
+        Ok(path) => Ok(Some(path)),
+        Err(TmpError::HomeNotSet) if !cli.config_given => {
+            warn!(
+                "HOME is not set, using built-in kinds without {config}",
+                config = cli.config
+            );
+            Ok(None)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn load_app(cli: &Cli, registry: Option<Registry>) -> Result<Tmp> {
+    let config_path = user_config(cli)?;
+
+    debug!("Resolved config path: {config_path:?}");
+
+    // Without a config the built-in kinds are used, but a path given with --config must exist
+    if let Some(path) = &config_path
+        && cli.config_given
+        && !path.exists()
+    {
+        return Err(TmpError::ConfigNotFound(path.clone()).into());
+    }
+
+    let cwd = std::env::current_dir().context("Failed to determine current directory")?;
+    let trust = trust_store().ok();
+    let config = config::load_layered(config_path.as_deref(), &cwd, trust.as_ref())
+        .with_context(|| format!("Failed to load config from {config}", config = cli.config))?;
+
+    let app = Tmp::new(config)?;
+    Ok(match registry {
+        Some(registry) => app.with_registry(registry),
+        None => app,
+    })
+}
+
+/// Print created paths on stdout and failures on stderr, failing with the first error
+///
+/// Returns the paths of the files that were created and kept.
+fn report_batch(app: &Tmp, report: BatchReport, options: &CreateOptions) -> Result<Vec<PathBuf>> {
+    let total = report.results.len();
+    let mut first_error = None;
+    let mut failed = 0;
+    let mut created = Vec::new();
+
+    for (entry, result) in report.results {
+        match result {
+            Ok(path) if options.dry_run => {
+                println!("would create: {path}", path = path.display());
+                if !options.no_hooks {
+                    for command in app.hook_preview(&entry.kind, &path, &options.vars)? {
+                        println!("would run: {command}");
+                    }
+                }
+            }
+            Ok(path) if report.rolled_back.contains(&path) => {}
+            // Only the path goes to stdout so `vim $(tmp py)` works
+            Ok(path) => {
+                println!("{path}", path = path.display());
+                created.push(path);
+            }
+            Err(e) => {
+                failed += 1;
+                error!(kind = entry.kind.as_str(), outcome = "failed"; "Failed to create file: {e}");
+                // The file exists despite the error, so its path is printed like any other
+                let e = match e.created_path() {
+                    Some(path) => {
+                        if !report.rolled_back.iter().any(|rolled_back| rolled_back == path) {
+                            println!("{path}", path = path.display());
+                        }
+                        eyre::Report::new(e)
+                    }
+                    None => eyre::Report::new(e)
+                        .wrap_err(format!("Failed to create file of kind: {kind}", kind = entry.kind)),
+                };
+                if total > 1 {
+                    eprintln!(
+                        "tmp: {kind} {name}: {e:#}",
+                        kind = entry.kind,
+                        name = entry.name.as_deref().unwrap_or("(default name)")
+                    );
+                }
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    for path in &report.rolled_back {
+        eprintln!("tmp: rolled back {path}", path = path.display());
+    }
+
+    match first_error {
+        Some(e) if total > 1 => Err(e.wrap_err(format!("{failed} of {total} files failed"))),
+        Some(e) => Err(e),
+        None => Ok(created),
+    }
+}
+
+/// Write a script that registers `tmp` for dynamic completion in `shell`
+fn write_completions(shell: clap_complete::Shell, out: &mut dyn Write) -> std::io::Result<()> {
+    let completer = std::env::current_exe()?;
+    let shells = clap_complete::env::Shells::builtins();
+    let registration = shells
+        .completer(&shell.to_string())
+        .ok_or_else(|| std::io::Error::other(format!("no completion support for {shell}")))?;
+    registration.write_registration("COMPLETE", "tmp", "tmp", &completer.to_string_lossy(), out)
+}
+
+/// `registry`, for the commands that can't work without the history
+fn need_registry(registry: Option<&Registry>) -> Result<&Registry> {
+    registry
+        .ok_or_else(|| eyre::Report::new(TmpError::HomeNotSet).wrap_err("The history of created files is unavailable"))
+}
+
+fn run_command(command: &Command, registry: Option<&Registry>, cli: &Cli) -> Result<()> {
+    let config_path = cli.config.as_str();
+    match command {
+        Command::Apply {
+            manifest,
+            atomic,
+            dry_run,
+            parents,
+            no_hooks,
+        } => {
+            info!("Applying manifest: {manifest}");
+            let app = load_app(cli, registry.cloned())?;
+            let manifest = load_manifest(Path::new(manifest))?;
+            let options = CreateOptions::default()
+                .with_dry_run(*dry_run)
+                .with_parents(*parents)
+                .with_no_hooks(*no_hooks);
+            let report = app.create_batch(&manifest.files, &options, *atomic);
+            report_batch(&app, report, &options)?;
+        }
+        Command::History { limit } => {
+            info!("Showing creation history");
+            let entries = need_registry(registry)?.load()?;
+            let limit = limit.unwrap_or(entries.len());
+            for entry in entries.iter().rev().take(limit) {
+                let expires = entry
+                    .expires
+                    .map(|expires| format!("  (expires {})", expires.format("%Y-%m-%d %H:%M:%S")))
+                    .unwrap_or_default();
+                let trashed = if entry.trashed.is_some() { "  (trashed)" } else { "" };
+                println!(
+                    "{created}  {kind:<12} {path}{expires}{trashed}",
+                    created = entry.created.format("%Y-%m-%d %H:%M:%S"),
+                    kind = entry.kind,
+                    path = entry.path.display()
+                );
+            }
+        }
+        Command::Rm {
+            last,
+            paths,
+            force,
+            trash,
+            prune,
+        } => {
+            let registry = need_registry(registry)?;
+            let options = DeleteOptions::default()
+                .with_force(*force)
+                .with_trash(trash.then(Trash::home).transpose()?)
+                .with_prune(*prune);
+            let targets: Vec<PathBuf> = if *last {
+                let entry = registry
+                    .last()?
+                    .ok_or_else(|| eyre::eyre!("No files have been created by tmp"))?;
+                vec![entry.path]
+            } else {
+                paths.iter().map(PathBuf::from).collect()
+            };
+
+            for path in targets {
+                let entry = registry
+                    .find(&path)?
+                    .ok_or_else(|| eyre::eyre!("File {path:?} was not created by tmp"))?;
+                info!("Remove mode: deleting registered file: {path:?}", path = entry.path);
+
+                if entry.path.exists() {
+                    remove_recorded_file(&entry, &options, registry)?;
+                } else {
+                    warn!("File {path:?} does not exist, forgetting it", path = entry.path);
+                    registry.forget(&entry.path)?;
+                }
+            }
+        }
+        Command::Restore { path } => {
+            let registry = need_registry(registry)?;
+            let entry = registry
+                .find_trashed(path.as_deref().map(Path::new))?
+                .ok_or_else(|| eyre::eyre!("No trashed files to restore"))?;
+            let location = entry.trashed.as_deref().unwrap_or(&entry.path);
+
+            Trash::home()?
+                .restore(location, &entry.path)
+                .context("Cannot restore")?;
+            registry.mark_restored(location)?;
+            println!("{path}", path = entry.path.display());
+        }
+        Command::Gc { dry_run } => {
+            info!("Collecting expired files (dry run: {dry_run})");
+            let report = need_registry(registry)?.collect_garbage(Local::now(), *dry_run)?;
+            let verb = if *dry_run { "would delete" } else { "deleted" };
+            for path in &report.deleted {
+                println!("{verb}: {path}", path = path.display());
+            }
+            for path in &report.modified {
+                println!("modified since creation, keeping: {path}", path = path.display());
+            }
+            for path in &report.missing {
+                debug!("Expired file already gone: {path:?}");
+            }
+        }
+        Command::Completions { shell } => {
+            info!("Generating {shell} completions");
+            write_completions(*shell, &mut std::io::stdout()).context("Failed to write completions")?;
+        }
+        Command::Init { project } => {
+            let path = if *project {
+                PathBuf::from(config::PROJECT_CONFIG)
+            } else {
+                config::expand_tilde(config_path)?
+            };
+            config::write_starter(&path, *project)?;
+            // Nothing in the starter runs commands, so a fresh project config starts out trusted
+            if *project && let Err(e) = trust_store().and_then(|trust| Ok(trust.add(&path)?)) {
+                warn!("Can't trust {path:?}: {e:#}");
+            }
+            println!("{path}", path = path.display());
+        }
+        Command::Trust { path, revoke } => {
+            let path = match path {
+                Some(path) => config::expand_tilde(path)?,
+                None => {
+                    let cwd = std::env::current_dir().context("Failed to determine current directory")?;
+                    config::find_project_config(&cwd).ok_or_else(|| {
+                        eyre::eyre!("No {name} in {cwd:?} or its parents", name = config::PROJECT_CONFIG)
+                    })?
+                }
+            };
+            let trust = trust_store()?;
+            if *revoke {
+                if trust.revoke(&path)? {
+                    println!("No longer trusting {path}", path = path.display());
+                } else {
+                    println!("{path} was not trusted", path = path.display());
+                }
+            } else {
+                config::load_config(&path)?;
+                trust.add(&path)?;
+                println!("Trusted {path}", path = path.display());
+            }
+        }
+        Command::AddKind {
+            from,
+            name,
+            description,
+            templatize,
+            stem,
+            yes,
+            vars,
+            tags,
+            project,
+        } => {
+            let path = if *project {
+                let cwd = std::env::current_dir().context("Failed to determine current directory")?;
+                config::find_project_config(&cwd).unwrap_or_else(|| cwd.join(config::PROJECT_CONFIG))
+            } else {
+                config::expand_tilde(config_path)?
+            };
+            let source = config::expand_tilde(from)?;
+            let cwd = std::env::current_dir().context("Failed to determine current directory")?;
+            let templates = config::load_layered(user_config(cli)?.as_deref(), &cwd, None)?.templates;
+            let (mut kind, escaped) = config_edit::kind_from_file(&source, name, &templates)?;
+            if !escaped.is_empty() {
+                eprintln!("Escaped, so new files keep them as written: {}", escaped.join(", "));
+            }
+            kind.description = description.clone();
+            kind.tags = tags.clone();
+            if *templatize {
+                let vars: Vars = vars.iter().cloned().collect();
+                let mut templatized = kind.clone();
+                let replacements = config_edit::templatize(&mut templatized, &source, &vars, *stem);
+                if replacements.is_empty() {
+                    eprintln!("Nothing to templatize in {source:?}");
+                } else if confirm_replacements(&kind.content, &replacements, *yes)? {
+                    kind = templatized;
+                } else {
+                    eprintln!("Keeping {source:?} as written");
+                }
+            }
+            // An edit made through tmp doesn't need another review
+            let trust = trust_store().ok().filter(|trust| *project && trust.is_trusted(&path));
+            config_edit::add_kind(&path, &kind)?;
+            if let Some(trust) = trust {
+                trust.add(&path)?;
+            }
+            println!("Added kind '{name}' to {path}", path = path.display());
+        }
+        Command::Edit { kind } => {
+            let app = load_app(cli, None)?;
+            let origin = app
+                .find_kind(kind)
+                .ok_or_else(|| TmpError::UnknownKind(kind.clone()))?
+                .origin;
+            let path = match origin {
+                Origin::User => config::expand_tilde(config_path)?,
+                Origin::Project => {
+                    let cwd = std::env::current_dir().context("Failed to determine current directory")?;
+                    config::find_project_config(&cwd).ok_or_else(|| {
+                        eyre::eyre!(
+                            "Kind '{kind}' comes from a project config, but no {} was found",
+                            config::PROJECT_CONFIG
+                        )
+                    })?
+                }
+                Origin::Builtin => {
+                    return Err(eyre::eyre!(
+                        "Kind '{kind}' is built in; run `tmp init` to get an editable copy of the built-in kinds"
+                    ));
+                }
+            };
+            let text = std::fs::read_to_string(&path).with_context(|| format!("Failed to read {path:?}"))?;
+            editor::open(&path, config_edit::kind_line(&text, kind))?;
+            // Catch mistakes while the edit is fresh rather than on the next run
+            config::load_config(&path)?;
+        }
+        Command::List {
+            tags,
+            suffix,
+            sort,
+            group,
+        } => {
+            let app = load_app(cli, None)?;
+            let filter = KindFilter {
+                tags: tags.clone(),
+                suffix: suffix.clone(),
+            };
+            let mut kinds: Vec<&Kind> = app.kinds().iter().filter(|kind| filter.matches(kind)).collect();
+            if *sort == ListOrder::Name {
+                kinds.sort_by(|a, b| a.name.cmp(&b.name));
+            }
+            list_kinds(&kinds, *group, &mut std::io::stdout()).context("Failed to list kinds")?;
+        }
+        Command::Man => {
+            man::write(&mut std::io::stdout()).context("Failed to write man page")?;
+        }
+        Command::Which { path } => {
+            let registry = need_registry(registry)?;
+            let entry = registry
+                .find(Path::new(path))?
+                .ok_or_else(|| eyre::eyre!("File {path:?} was not created by tmp"))?;
+            let modified = entry.path.exists() && registry::hash_file(&entry.path)? != entry.hash;
+            if modified {
+                println!("{kind} (modified since creation)", kind = entry.kind);
+            } else {
+                println!("{kind}", kind = entry.kind);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// One line per kind: name, origin, description and tags, or with `group` indented under each tag
+fn list_kinds(kinds: &[&Kind], group: bool, out: &mut dyn Write) -> std::io::Result<()> {
+    if !group {
+        for kind in kinds {
+            let tags = if kind.tags.is_empty() {
+                String::new()
+            } else {
+                format!(" [{}]", kind.tags.join(", "))
+            };
+            writeln!(out, "{line}{tags}", line = kind_line(kind).trim_end())?;
+        }
+        return Ok(());
+    }
+
+    for (index, (tag, kinds)) in config::group_by_tag(kinds).into_iter().enumerate() {
+        if index > 0 {
+            writeln!(out)?;
+        }
+        writeln!(out, "{tag}:", tag = tag.unwrap_or("(untagged)"))?;
+        for kind in kinds {
+            writeln!(out, "  {line}", line = kind_line(kind).trim_end())?;
+        }
+    }
+    Ok(())
+}
+
+fn kind_line(kind: &Kind) -> String {
+    format!(
+        "{name:<12} {origin:<8} {description}",
+        name = kind.name,
+        origin = kind.origin,
+        description = kind.description.as_deref().unwrap_or_default()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_exit_code_from_chain() {
+        let report =
+            eyre::Report::new(TmpError::TargetExists(PathBuf::from("tmp.py"))).wrap_err("Failed to create file");
+        assert_eq!(exit_code(&report), exit::CONFLICT);
+
+        let report = eyre::Report::new(TmpError::UnknownKind("nope".to_string()));
+        assert_eq!(exit_code(&report), exit::UNKNOWN_KIND);
+
+        let io = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied");
+        let report = eyre::Report::new(io).wrap_err("Failed to write");
+        assert_eq!(exit_code(&report), exit::IO);
+
+        let yaml = serde_yaml::from_str::<Vec<String>>("[").unwrap_err();
+        let report = eyre::Report::new(yaml).wrap_err("Failed to parse YAML config");
+        assert_eq!(exit_code(&report), exit::CONFIG);
+
+        assert_eq!(exit_code(&eyre::eyre!("something else")), exit::FAILURE);
+    }
+
+    #[test]
+    fn test_list_kinds_shows_origin() {
+        let kinds = [
+            Kind::new("py")
+                .with_description("Python script")
+                .with_origin(Origin::Project),
+            Kind::new("sh").with_origin(Origin::Builtin),
+        ];
+
+        let kinds: Vec<&Kind> = kinds.iter().collect();
+
+        let mut out = Vec::new();
+        list_kinds(&kinds, false, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "py           project  Python script\nsh           builtin\n"
+        );
+    }
+
+    #[test]
+    fn test_list_kinds_grouped_by_tag() {
+        let kinds = [Kind::new("py").with_tags(["python", "script"]), Kind::new("txt")];
+        let kinds: Vec<&Kind> = kinds.iter().collect();
+
+        let mut out = Vec::new();
+        list_kinds(&kinds, false, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "py           user [python, script]\ntxt          user\n"
+        );
+
+        let mut out = Vec::new();
+        list_kinds(&kinds, true, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "python:\n  py           user\n\nscript:\n  py           user\n\n(untagged):\n  txt          user\n"
+        );
+    }
+
+    #[test]
+    fn test_write_completions_registers_the_binary() {
+        let mut bash = Vec::new();
+        write_completions(clap_complete::Shell::Bash, &mut bash).unwrap();
+        let bash = String::from_utf8(bash).unwrap();
+        assert!(bash.contains("COMPLETE=\"bash\""));
+        assert!(bash.contains(&std::env::current_exe().unwrap().to_string_lossy().to_string()));
+    }
+
+    #[test]
+    fn test_kinds_for_completion_follow_config_and_cwd() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let config = tempdir.path().join("tmp.yml");
+        std::fs::write(
+            &config,
+            "templates: {}\nkinds:\n  notes:\n    suffix: md\n    content: \"\"\n    description: Notes\n",
+        )
+        .unwrap();
+        let project = tempdir.path().join("project");
+        std::fs::create_dir(&project).unwrap();
+        std::fs::write(
+            project.join(".tmp.yml"),
+            "templates: {}\nkinds:\n  fixture:\n    suffix: json\n    content: \"{}\"\n",
+        )
+        .unwrap();
+
+        let names = |cwd: &Path| -> Vec<String> {
+            cli::kinds_for_completion(&config.to_string_lossy(), cwd)
+                .iter()
+                .map(|candidate| candidate.get_value().to_string_lossy().to_string())
+                .collect()
+        };
+        assert!(names(tempdir.path()).contains(&"notes".to_string()));
+        assert!(!names(tempdir.path()).contains(&"fixture".to_string()));
+        assert!(names(&project).contains(&"fixture".to_string()));
+
+        let notes = cli::kinds_for_completion(&config.to_string_lossy(), tempdir.path())
+            .into_iter()
+            .find(|candidate| candidate.get_value() == "notes")
+            .unwrap();
+        assert_eq!(notes.get_help().unwrap().to_string(), "Notes");
+    }
+}
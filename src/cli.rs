@@ -1,4 +1,21 @@
-use clap::Parser;
+use crate::Tmp;
+use crate::config::{self, DEFAULT_CONFIG};
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum, ValueHint};
+use clap_complete::{ArgValueCandidates, CompletionCandidate, Shell};
+use std::ffi::OsString;
+use std::path::Path;
+
+/// How `tmp list` orders kinds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ListOrder {
+    /// By each kind's `order:`, then as declared in the config
+    #[default]
+    Config,
+    /// Alphabetically by name
+    Name,
+}
 
 #[derive(Parser)]
 #[command(
@@ -6,35 +23,553 @@ use clap::Parser;
     about = "Helper function to quickly make file types defined in the config file",
     version = env!("GIT_DESCRIBE"),
     author = "Scott Idler <scott.a.idler@gmail.com>",
-    after_help = "Logs are written to: ~/.local/share/tmp/tmp.log"
+    after_help = "Logs are written to ~/.local/share/tmp/tmp.log (see --log-file); set TMP_LOG=debug for more detail.\n\
+                  The log is rotated once it reaches TMP_LOG_MAX_SIZE (default 1M), keeping TMP_LOG_KEEP old files (default 3).\n\n\
+                  Exit codes: 0 success, 1 other failure, 2 usage error, 3 unknown kind,\n\
+                  4 file already exists, 5 I/O error, 6 config error, 7 kind requirements not met,\n\
+                  8 permission denied\n\n\
+                  A KIND or NAME spelled like a subcommand goes after `--`: `tmp -- list`, `tmp py -- history`.",
+    override_usage = "tmp [OPTIONS] [KIND] [NAME]...\n       tmp [OPTIONS] <COMMAND>",
+    subcommand_negates_reqs = true
 )]
 pub struct Cli {
     /// Config filepath
     #[arg(
         long,
         value_name = "FILEPATH",
-        default_value = "~/.config/tmp/tmp.yml",
-        help = "Config filepath"
+        value_hint = ValueHint::FilePath,
+        default_value = DEFAULT_CONFIG,
+        help = "Config filepath",
+        global = true
     )]
     pub config: String,
 
+    /// Whether `--config` was given rather than defaulted; a given config must exist
+    #[arg(skip)]
+    pub config_given: bool,
+
+    /// Mirror logs to stderr
+    #[arg(
+        short = 'v',
+        long,
+        action = clap::ArgAction::Count,
+        global = true,
+        help = "Mirror logs to stderr: -v info, -vv debug, -vvv trace"
+    )]
+    pub verbose: u8,
+
+    /// Print nothing but results and errors
+    #[arg(
+        short = 'q',
+        long,
+        global = true,
+        conflicts_with = "verbose",
+        help = "Print nothing on stderr but errors"
+    )]
+    pub quiet: bool,
+
+    /// Log file path
+    #[arg(
+        long,
+        value_name = "FILEPATH",
+        value_hint = ValueHint::FilePath,
+        global = true,
+        help = "Write logs here instead of ~/.local/share/tmp/tmp.log"
+    )]
+    pub log_file: Option<String>,
+
+    /// Log file format
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_enum,
+        default_value_t,
+        env = "TMP_LOG_FORMAT",
+        global = true,
+        help = "Log file format; json writes one object per line with timestamp, level, kind, path and outcome"
+    )]
+    pub log_format: crate::logging::LogFormat,
+
     /// Only print contents of the file to be made
     #[arg(short = 'N', long, help = "Only print contents of the file to be made")]
     pub nerf: bool,
 
-    /// Delete filename
-    #[arg(short = 'r', long, help = "Delete filename")]
+    /// Open the new file in $VISUAL/$EDITOR
+    #[arg(
+        short = 'e',
+        long,
+        conflicts_with_all = ["nerf", "rm"],
+        help = "Open the new file in $VISUAL/$EDITOR, at the {cursor} marker if the kind has one"
+    )]
+    pub edit: bool,
+
+    /// Create missing parent directories
+    #[arg(short = 'p', long, help = "Create missing parent directories of NAME")]
+    pub parents: bool,
+
+    /// Use the current directory instead of the kind's configured dir
+    #[arg(long, help = "Use the current directory instead of the kind's dir or default_dir")]
+    pub here: bool,
+
+    /// Skip the kind's post_create hooks
+    #[arg(long, help = "Skip the kind's post_create hooks")]
+    pub no_hooks: bool,
+
+    /// Show what would be created and run without touching anything
+    #[arg(
+        short = 'n',
+        long,
+        conflicts_with_all = ["nerf", "rm"],
+        help = "Show the file that would be created and the hooks that would run"
+    )]
+    pub dry_run: bool,
+
+    /// Write to this path instead of NAME, or `-` for stdout
+    #[arg(
+        short = 'o',
+        long,
+        value_name = "FILE",
+        value_hint = ValueHint::FilePath,
+        conflicts_with_all = ["rm", "edit"],
+        help = "Create FILE instead of NAME; `-` renders the kind to stdout, using NAME for file variables"
+    )]
+    pub output: Option<String>,
+
+    /// Read stdin into the {tmp.stdin} placeholder
+    #[arg(long, help = "Read standard input into the kind's {tmp.stdin} placeholder")]
+    pub stdin: bool,
+
+    /// Set a template variable
+    #[arg(
+        long = "var",
+        value_name = "KEY=VALUE",
+        value_parser = crate::vars::parse_key_value,
+        help = "Set {KEY} to VALUE in the content and hooks (repeatable)"
+    )]
+    pub vars: Vec<(String, String)>,
+
+    /// Delete the named files, or without NAME the kind's most recently created file
+    #[arg(
+        short = 'r',
+        long,
+        help = "Delete the named files, or without NAME the kind's most recently created file"
+    )]
     pub rm: bool,
 
+    /// Delete even if the file was modified or not created by tmp
+    #[arg(
+        short = 'f',
+        long,
+        requires = "rm",
+        help = "With --rm, delete even if the file was modified or not created by tmp"
+    )]
+    pub force: bool,
+
+    /// Move the file to the trash instead of deleting it
+    #[arg(
+        long,
+        requires = "rm",
+        help = "With --rm, move the file to the trash instead of deleting it"
+    )]
+    pub trash: bool,
+
+    /// Remove empty parent directories that tmp created for the file
+    #[arg(
+        long,
+        requires = "rm",
+        help = "With --rm, remove empty parent directories tmp created"
+    )]
+    pub prune: bool,
+
     /// Set the value to chmod the file to
     #[arg(short = 'c', long, value_name = "MODE", help = "Set the value to chmod the file to")]
     pub chmod: Option<String>,
 
-    /// Choose which kind of tmp file
-    #[arg(value_name = "KIND", help = "Choose which kind of tmp file")]
-    pub kind: String,
+    /// Expire the file after this long, overriding the kind's ttl
+    #[arg(
+        long,
+        value_name = "DURATION",
+        help = "Let `tmp gc` delete the file after this long (e.g. 30m, 2h, 7d)"
+    )]
+    pub ttl: Option<String>,
+
+    /// Choose which kind of tmp file; omit it on a terminal to pick one interactively
+    #[arg(
+        value_name = "KIND",
+        index = 1,
+        add = ArgValueCandidates::new(kind_candidates),
+        help = "Choose which kind of tmp file; omit it on a terminal to pick one interactively"
+    )]
+    pub kind: Option<String>,
+
+    /// Command that chooses a kind when KIND is omitted, instead of the built-in picker
+    #[arg(
+        long,
+        value_name = "CMD",
+        env = "TMP_PICKER",
+        help = "Command that picks a kind when KIND is omitted, e.g. \"fzf --preview 'tmp {1} --nerf'\"; \
+                it reads NAME<TAB>DESCRIPTION lines and prints the chosen one"
+    )]
+    pub picker: Option<String>,
 
     /// Optionally name the script
-    #[arg(value_name = "NAME", help = "Optionally name the script")]
-    pub name: Option<String>,
+    #[arg(
+        value_name = "NAME",
+        index = 2,
+        help = "Optionally name the script; several names create several files"
+    )]
+    pub names: Vec<String>,
+
+    /// Roll back every file created by this invocation if one fails
+    #[arg(long, help = "With several names, delete the files already created if one fails")]
+    pub atomic: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Kinds offered when completing KIND, from the config in effect for the line being completed
+fn kind_candidates() -> Vec<CompletionCandidate> {
+    // Completion runs as `tmp -- tmp [ARGS]...`, so a `--config` on that line is visible here
+    let args: Vec<String> = std::env::args().skip_while(|arg| arg != "--").collect();
+    let config_path = args
+        .windows(2)
+        .find_map(|pair| (pair[0] == "--config").then(|| pair[1].clone()))
+        .or_else(|| {
+            args.iter()
+                .find_map(|arg| arg.strip_prefix("--config=").map(str::to_string))
+        })
+        .unwrap_or_else(|| DEFAULT_CONFIG.to_string());
+
+    match std::env::current_dir() {
+        Ok(cwd) => kinds_for_completion(&config_path, &cwd),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Every kind of the layered config at `config_path` and `cwd`, described; none if it doesn't load
+pub fn kinds_for_completion(config_path: &str, cwd: &Path) -> Vec<CompletionCandidate> {
+    let tmp = config::expand_tilde(config_path)
+        .and_then(|path| config::load_layered(Some(&path), cwd, None))
+        .and_then(Tmp::new);
+    let Ok(tmp) = tmp else {
+        return Vec::new();
+    };
+
+    tmp.kinds()
+        .iter()
+        .map(|kind| CompletionCandidate::new(&kind.name).help(kind.description.clone().map(Into::into)))
+        .collect()
+}
+
+impl Cli {
+    /// Parse `args`, rejecting a KIND followed by what clap took for a subcommand
+    ///
+    /// Global flags may come before a subcommand, so `tmp py list` reads `list` as the subcommand;
+    /// a NAME spelled like one has to follow `--` instead.
+    pub fn try_parse_args<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let mut command = Self::command();
+        let matches = command.try_get_matches_from_mut(args)?;
+        let mut cli = Self::from_arg_matches(&matches)?;
+        cli.config_given = matches.value_source("config") != Some(ValueSource::DefaultValue);
+
+        if let (Some(kind), Some(name)) = (&cli.kind, matches.subcommand_name()) {
+            return Err(command.error(
+                ErrorKind::ArgumentConflict,
+                format!("'{name}' is a subcommand; to create a file named {name}, use `tmp {kind} -- {name}`"),
+            ));
+        }
+        Ok(cli)
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Show files created by tmp, newest first
+    History {
+        /// Maximum number of entries to show
+        #[arg(short = 'n', long, value_name = "COUNT", help = "Maximum number of entries to show")]
+        limit: Option<usize>,
+    },
+
+    /// Delete files recorded in the registry
+    Rm {
+        /// Delete the most recently created file
+        #[arg(long, help = "Delete the most recently created file", conflicts_with = "paths")]
+        last: bool,
+
+        /// Paths of registered files to delete
+        #[arg(
+            value_name = "PATH",
+            value_hint = ValueHint::FilePath,
+            help = "Paths of registered files to delete",
+            required_unless_present = "last"
+        )]
+        paths: Vec<String>,
+
+        /// Delete even if the file was modified since creation
+        #[arg(short = 'f', long, help = "Delete even if the file was modified since creation")]
+        force: bool,
+
+        /// Move files to the trash instead of deleting them
+        #[arg(long, help = "Move files to the trash instead of deleting them")]
+        trash: bool,
+
+        /// Remove empty parent directories that tmp created for the files
+        #[arg(long, help = "Remove empty parent directories that tmp created for the files")]
+        prune: bool,
+    },
+
+    /// Restore a file that was moved to the trash
+    Restore {
+        /// Original path of the file; defaults to the most recently trashed file
+        #[arg(
+            value_name = "PATH",
+            value_hint = ValueHint::FilePath,
+            help = "Original path of the file; defaults to the most recently trashed file"
+        )]
+        path: Option<String>,
+    },
+
+    /// Delete expired files that are unmodified since creation
+    Gc {
+        /// Only report what would be deleted
+        #[arg(short = 'n', long, help = "Only report what would be deleted")]
+        dry_run: bool,
+    },
+
+    /// Create every file listed in a YAML manifest
+    Apply {
+        /// Manifest listing kind, name and vars for each file
+        #[arg(
+            value_name = "MANIFEST",
+            value_hint = ValueHint::FilePath,
+            help = "Manifest listing kind, name and vars for each file"
+        )]
+        manifest: String,
+
+        /// Delete the files already created if one fails
+        #[arg(long, help = "Delete the files already created if one fails")]
+        atomic: bool,
+
+        /// Show what would be created without touching anything
+        #[arg(short = 'n', long, help = "Show what would be created without touching anything")]
+        dry_run: bool,
+
+        /// Create missing parent directories
+        #[arg(short = 'p', long, help = "Create missing parent directories")]
+        parents: bool,
+
+        /// Skip post_create hooks
+        #[arg(long, help = "Skip post_create hooks")]
+        no_hooks: bool,
+    },
+
+    /// Print a script that registers shell completions
+    ///
+    /// Kind names are looked up on each TAB, so they follow the config and the current directory.
+    /// Source it from your shell's rc file, e.g. `source <(tmp completions bash)`.
+    Completions {
+        /// Shell to generate completions for
+        #[arg(value_name = "SHELL", help = "Shell to generate completions for")]
+        shell: Shell,
+    },
+
+    /// Write a commented starter config with common kinds
+    Init {
+        /// Create a repo-local .tmp.yml in the current directory instead
+        #[arg(
+            long,
+            help = "Create a repo-local .tmp.yml in the current directory instead of the user config"
+        )]
+        project: bool,
+    },
+
+    /// Allow the nearest .tmp.yml to run post_create hooks and check requires.command
+    ///
+    /// Trust is pinned to the file's current content; after any edit, review it and trust it again.
+    Trust {
+        /// Project config to trust; defaults to the nearest .tmp.yml
+        #[arg(value_name = "PATH", value_hint = ValueHint::FilePath, help = "Project config to trust; defaults to the nearest .tmp.yml")]
+        path: Option<String>,
+
+        /// Stop trusting it instead
+        #[arg(long, help = "Stop trusting it instead")]
+        revoke: bool,
+    },
+
+    /// Add a kind to the config, copying an existing file
+    ///
+    /// The suffix comes from the file's extension and chmod from its mode. Comments in the
+    /// config are kept.
+    AddKind {
+        /// File whose content becomes the kind's content
+        #[arg(
+            long,
+            value_name = "FILE",
+            value_hint = ValueHint::FilePath,
+            help = "File whose content becomes the kind's content"
+        )]
+        from: String,
+
+        /// Name of the new kind
+        #[arg(long, value_name = "KIND", help = "Name of the new kind")]
+        name: String,
+
+        /// One line shown next to the kind in listings and completions
+        #[arg(long, help = "One line shown next to the kind in listings and completions")]
+        description: Option<String>,
+
+        /// Replace the file's name, today's date and --var values with placeholders, after confirming
+        #[arg(
+            long,
+            help = "Replace the file's name, today's date and --var values with {tmp.name}, {tmp.date} and {tmp.KEY}, after confirming"
+        )]
+        templatize: bool,
+
+        /// Also replace the file's stem with --templatize, which often matches names in the code
+        #[arg(
+            long,
+            requires = "templatize",
+            help = "Also replace the file's stem with {tmp.stem}; it often matches names in the code too"
+        )]
+        stem: bool,
+
+        /// Apply --templatize replacements without asking
+        #[arg(
+            short = 'y',
+            long,
+            requires = "templatize",
+            help = "Apply --templatize replacements without asking"
+        )]
+        yes: bool,
+
+        /// Values to turn back into {tmp.KEY} placeholders with --templatize (repeatable)
+        #[arg(
+            long = "var",
+            value_name = "KEY=VALUE",
+            value_parser = crate::vars::parse_key_value,
+            requires = "templatize",
+            help = "Turn VALUE back into {tmp.KEY} with --templatize, e.g. --var author='Ada Lovelace' (repeatable)"
+        )]
+        vars: Vec<(String, String)>,
+
+        /// Tags for the new kind (repeatable)
+        #[arg(
+            long = "tag",
+            value_name = "TAG",
+            help = "Tag the new kind, e.g. --tag python (repeatable)"
+        )]
+        tags: Vec<String>,
+
+        /// Add the kind to the project's .tmp.yml instead of the user config
+        #[arg(long, help = "Add the kind to the project's .tmp.yml instead of the user config")]
+        project: bool,
+    },
+
+    /// Open the config file that defines a kind, at its definition
+    #[command(alias = "edit-kind")]
+    Edit {
+        /// Kind to edit
+        #[arg(value_name = "KIND", add = ArgValueCandidates::new(kind_candidates), help = "Kind to edit")]
+        kind: String,
+    },
+
+    /// List the available kinds and where each is defined
+    List {
+        /// Only kinds with this tag; repeat to require several
+        #[arg(
+            long = "tag",
+            value_name = "TAG",
+            help = "Only kinds with this tag; repeat to require several"
+        )]
+        tags: Vec<String>,
+
+        /// Only kinds with this suffix
+        #[arg(long, value_name = "SUFFIX", help = "Only kinds with this suffix, e.g. py")]
+        suffix: Option<String>,
+
+        /// Order kinds by their `order:` and config position, or alphabetically
+        #[arg(
+            long,
+            value_name = "ORDER",
+            value_enum,
+            default_value_t,
+            help = "Order kinds by their order: and position in the config, or alphabetically by name"
+        )]
+        sort: ListOrder,
+
+        /// Group kinds under their tags
+        #[arg(short = 'g', long, help = "Group kinds under their tags")]
+        group: bool,
+    },
+
+    /// Print the man page in roff format, e.g. `tmp man > tmp.1`
+    Man,
+
+    /// Show which kind created a file
+    Which {
+        /// Path of the file to look up
+        #[arg(value_name = "PATH", value_hint = ValueHint::FilePath, help = "Path of the file to look up")]
+        path: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_global_flags_before_subcommands() {
+        let cli = Cli::try_parse_args(["tmp", "-v", "list"]).unwrap();
+        assert_eq!(cli.verbose, 1);
+        assert!(matches!(cli.command, Some(Command::List { .. })));
+
+        let cli = Cli::try_parse_args(["tmp", "--config", "f", "history"]).unwrap();
+        assert_eq!(cli.config, "f");
+        assert!(matches!(cli.command, Some(Command::History { .. })));
+
+        let cli = Cli::try_parse_args(["tmp", "--config", "f", "add-kind", "--from", "x.py", "--name", "x"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::AddKind { .. })));
+    }
+
+    #[test]
+    fn test_config_given() {
+        assert!(!Cli::try_parse_args(["tmp", "py"]).unwrap().config_given);
+        // Spelling out the default path still counts as asking for it
+        assert!(
+            Cli::try_parse_args(["tmp", "--config", DEFAULT_CONFIG, "py"])
+                .unwrap()
+                .config_given
+        );
+        assert!(
+            Cli::try_parse_args(["tmp", "list", "--config", "f"])
+                .unwrap()
+                .config_given
+        );
+        assert!(!Cli::try_parse_args(["tmp", "list"]).unwrap().config_given);
+    }
+
+    #[test]
+    fn test_subcommand_names_as_kinds_and_names() {
+        let cli = Cli::try_parse_args(["tmp", "-n", "py", "a"]).unwrap();
+        assert_eq!(cli.kind.as_deref(), Some("py"));
+        assert_eq!(cli.names, vec!["a"]);
+        assert!(cli.command.is_none());
+
+        let error = Cli::try_parse_args(["tmp", "py", "list"]).map(|_| ()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ArgumentConflict);
+
+        let cli = Cli::try_parse_args(["tmp", "py", "--", "list"]).unwrap();
+        assert_eq!(cli.names, vec!["list"]);
+        assert!(cli.command.is_none());
+
+        let cli = Cli::try_parse_args(["tmp", "--", "list", "x"]).unwrap();
+        assert_eq!(cli.kind.as_deref(), Some("list"));
+        assert_eq!(cli.names, vec!["x"]);
+    }
 }
@@ -1,31 +1,291 @@
-use eyre::{Context, Result};
-use log::{debug, error, info};
+use crate::error::{Context, Result, TmpError};
+use crate::requires::Requires;
+use crate::trust::Trust;
+use log::{debug, error, info, warn};
+use serde::de::{Error as _, MapAccess, Visitor};
 use serde::{Deserialize, Deserializer, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// File name of a repo-local config, found in the current directory or a parent
+pub const PROJECT_CONFIG: &str = ".tmp.yml";
+
+/// Config read when `--config` isn't given; unlike an explicit path it may be missing
+pub const DEFAULT_CONFIG: &str = "~/.config/tmp/tmp.yml";
+
+/// The starter config doubles as the built-in kinds, so `tmp init` writes out what works without one
+const STARTER_CONFIG: &str = include_str!("starter/user.yml");
+const STARTER_PROJECT_CONFIG: &str = include_str!("starter/project.yml");
+
+/// Where a kind was defined
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Origin {
+    /// Compiled into the binary
+    Builtin,
+    /// The user config, `~/.config/tmp/tmp.yml` by default
+    #[default]
+    User,
+    /// A `.tmp.yml` in the current directory or a parent
+    Project,
+}
+
+impl std::fmt::Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Origin::Builtin => "builtin",
+            Origin::User => "user",
+            Origin::Project => "project",
+        })
+    }
+}
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[non_exhaustive]
 pub struct Kind {
     pub name: String,
     pub chmod: Option<u32>,
     pub suffix: String,
     pub content: String,
+    /// How long files of this kind live before `tmp gc` may delete them
+    pub ttl: Option<Duration>,
+    /// Name pattern used when no NAME is given, e.g. `tmp-{date}-{n}`
+    pub default_name: Option<String>,
+    /// Always open files of this kind in the editor after creating them
+    pub open: bool,
+    /// Shell commands run in order after the file is written, e.g. `git add {path}`
+    pub post_create: Vec<String>,
+    /// Conditions checked before the file is written
+    pub requires: Requires,
+    /// Create missing parent directories by default
+    pub parents: bool,
+    /// Mode for parent directories created for this kind; the umask applies when unset
+    pub dir_mode: Option<u32>,
+    /// Directory that bare names are created in, e.g. `~/tmp/scratch` or `tests/{kind}`
+    pub dir: Option<String>,
+    /// One line shown next to the kind name in shell completions
+    pub description: Option<String>,
+    /// Labels for filtering and grouping kinds, e.g. `python`, `test` or `ops`
+    pub tags: Vec<String>,
+    /// Position in listings; kinds without one follow, in the order they're declared
+    pub order: Option<i32>,
+    /// Which config layer defined the kind
+    #[serde(skip)]
+    pub origin: Origin,
+    /// The project config the kind came from, while it isn't trusted to run commands
+    #[serde(skip)]
+    pub untrusted: Option<PathBuf>,
+    /// Where a relative `dir` starts: the project root for project kinds, the cwd when unset
+    #[serde(skip)]
+    pub root: Option<PathBuf>,
+}
+
+impl Kind {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.suffix = suffix.into();
+        self
+    }
+
+    pub fn with_content(mut self, content: impl Into<String>) -> Self {
+        self.content = content.into();
+        self
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn with_tags<T: Into<String>>(mut self, tags: impl IntoIterator<Item = T>) -> Self {
+        self.tags = tags.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_origin(mut self, origin: Origin) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Append the kind's suffix to `name` unless it already ends with it
+    pub fn filename(&self, name: &str) -> String {
+        if self.suffix.is_empty() {
+            return name.to_string();
+        }
+
+        let suffix_with_dot = format!(".{suffix}", suffix = self.suffix);
+        if name.ends_with(&suffix_with_dot) {
+            name.to_string()
+        } else {
+            format!("{name}.{suffix}", suffix = self.suffix)
+        }
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Which kinds a listing shows; an empty filter matches every kind
+#[derive(Debug, Clone, Default)]
+pub struct KindFilter {
+    /// Tags a kind must all have
+    pub tags: Vec<String>,
+    pub suffix: Option<String>,
+}
+
+impl KindFilter {
+    pub fn matches(&self, kind: &Kind) -> bool {
+        self.tags.iter().all(|tag| kind.tags.contains(tag))
+            && self
+                .suffix
+                .as_deref()
+                .is_none_or(|suffix| kind.suffix == suffix.trim_start_matches('.'))
+    }
+}
+
+/// `kinds` grouped by tag, tags in alphabetical order and untagged kinds last under `None`
+///
+/// A kind with several tags appears in each of their groups.
+pub fn group_by_tag<'a>(kinds: &[&'a Kind]) -> Vec<(Option<&'a str>, Vec<&'a Kind>)> {
+    let mut groups: BTreeMap<&str, Vec<&Kind>> = BTreeMap::new();
+    let mut untagged = Vec::new();
+    for &kind in kinds {
+        if kind.tags.is_empty() {
+            untagged.push(kind);
+        }
+        for tag in &kind.tags {
+            groups.entry(tag.as_str()).or_default().push(kind);
+        }
+    }
+
+    let mut grouped: Vec<(Option<&str>, Vec<&Kind>)> =
+        groups.into_iter().map(|(tag, kinds)| (Some(tag), kinds)).collect();
+    if !untagged.is_empty() {
+        grouped.push((None, untagged));
+    }
+    grouped
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct Config {
     #[serde(deserialize_with = "deserialize_kinds")]
     pub kinds: Vec<Kind>,
     pub templates: HashMap<String, String>,
+    /// Directory for bare names of kinds that don't set their own `dir`
+    #[serde(default)]
+    pub default_dir: Option<String>,
+    /// Where a relative `default_dir` starts, like `Kind::root`
+    #[serde(skip)]
+    pub default_dir_root: Option<PathBuf>,
+}
+
+impl Config {
+    /// Add `other` on top of this config: its kinds and templates replace ones with the same name
+    ///
+    /// `other`'s kinds come first in the order it declares them, followed by the kinds it doesn't
+    /// replace, so each kind sits where the highest layer declaring it puts it.
+    pub fn layer(&mut self, other: Config) {
+        let below = std::mem::replace(&mut self.kinds, other.kinds);
+        let kept: Vec<Kind> = below
+            .into_iter()
+            .filter(|kind| !self.kinds.iter().any(|k| k.name == kind.name))
+            .collect();
+        self.kinds.extend(kept);
+        self.templates.extend(other.templates);
+        if other.default_dir.is_some() {
+            self.default_dir = other.default_dir;
+            self.default_dir_root = other.default_dir_root;
+        }
+    }
+
+    fn with_origin(mut self, origin: Origin) -> Self {
+        for kind in &mut self.kinds {
+            kind.origin = origin;
+        }
+        self
+    }
+
+    /// Replace `{template}` placeholders in `content`, expanding templates that use other templates
+    ///
+    /// A doubled opening brace keeps a placeholder as it is: `{{template}` becomes `{template}`.
+    pub fn expand_templates(&self, content: &str) -> Result<String> {
+        self.expand_with_stack(content, &mut Vec::new())
+    }
+
+    fn expand_with_stack(&self, content: &str, stack: &mut Vec<String>) -> Result<String> {
+        let mut expanded = String::with_capacity(content.len());
+        let mut rest = content;
+        while let Some(start) = rest.find('{') {
+            expanded.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            let escaped = after.starts_with('{');
+            let placeholder = if escaped { &after[1..] } else { after };
+            let template = placeholder.find('}').and_then(|end| {
+                self.templates
+                    .get_key_value(&placeholder[..end])
+                    .map(|found| (end, found))
+            });
+            match template {
+                Some((end, _)) if escaped => {
+                    expanded.push('{');
+                    expanded.push_str(&placeholder[..=end]);
+                    rest = &placeholder[end + 1..];
+                }
+                Some((end, (name, template))) => {
+                    if stack.contains(name) {
+                        let mut cycle = stack.clone();
+                        cycle.push(name.clone());
+                        return Err(TmpError::TemplateCycle(cycle));
+                    }
+                    stack.push(name.clone());
+                    expanded.push_str(&self.expand_with_stack(template, stack)?);
+                    stack.pop();
+                    rest = &placeholder[end + 1..];
+                }
+                None => {
+                    expanded.push('{');
+                    rest = after;
+                }
+            }
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
 }
 
 fn deserialize_kinds<'de, D>(deserializer: D) -> Result<Vec<Kind>, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let map: HashMap<String, KindData> = HashMap::deserialize(deserializer)?;
-    Ok(map
+    // A map visitor rather than a HashMap, so kinds keep the order they're declared in
+    struct KindsVisitor;
+
+    impl<'de> Visitor<'de> for KindsVisitor {
+        type Value = Vec<(String, KindData)>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a map from kind name to kind")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut kinds: Self::Value = Vec::new();
+            while let Some((name, data)) = map.next_entry::<String, KindData>()? {
+                if kinds.iter().any(|(existing, _)| *existing == name) {
+                    return Err(A::Error::custom(format!("kind '{name}' is defined twice")));
+                }
+                kinds.push((name, data));
+            }
+            Ok(kinds)
+        }
+    }
+
+    deserializer
+        .deserialize_map(KindsVisitor)?
         .into_iter()
         .map(|(name, mut data)| {
             // Handle chmod values that are likely meant to be octal
@@ -35,17 +295,84 @@ where
                 data.chmod = Some(interpret_chmod_value(chmod));
             }
 
-            Kind {
+            let ttl = data
+                .ttl
+                .as_deref()
+                .map(parse_duration)
+                .transpose()
+                .map_err(|e| D::Error::custom(format!("kind '{name}': {e}")))?;
+
+            Ok(Kind {
                 name,
                 chmod: data.chmod,
                 suffix: data.suffix,
                 content: data.content,
-            }
+                ttl,
+                default_name: data.default_name,
+                open: data.open,
+                post_create: data.post_create,
+                requires: data.requires,
+                parents: data.parents,
+                dir_mode: data.dir_mode.map(interpret_chmod_value),
+                dir: data.dir,
+                description: data.description,
+                tags: data.tags,
+                order: data.order,
+                origin: Origin::default(),
+                untrusted: None,
+                root: None,
+            })
         })
-        .collect())
+        .collect()
 }
 
-fn interpret_chmod_value(value: u32) -> u32 {
+/// Parse a human duration such as `90s`, `30m`, `2h`, `7d`, `1w` or `1h30m`
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let invalid = |reason: String| TmpError::InvalidDuration {
+        value: value.to_string(),
+        reason,
+    };
+    if value.is_empty() {
+        return Err(invalid("empty duration".to_string()));
+    }
+
+    let mut total: u64 = 0;
+    let mut digits = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return Err(invalid(format!("unknown unit '{c}'"))),
+        };
+        if digits.is_empty() {
+            return Err(invalid(format!("missing number before '{c}'")));
+        }
+        total = digits
+            .parse::<u64>()
+            .ok()
+            .and_then(|amount| amount.checked_mul(unit))
+            .and_then(|seconds| total.checked_add(seconds))
+            .ok_or_else(|| invalid("too long".to_string()))?;
+        digits.clear();
+    }
+
+    if !digits.is_empty() {
+        return Err(invalid(format!("missing unit after '{digits}'")));
+    }
+
+    Ok(Duration::from_secs(total))
+}
+
+pub(crate) fn interpret_chmod_value(value: u32) -> u32 {
     // Check if the value looks like a common octal permission written as decimal
     // Common patterns: 644, 664, 755, 775, 777, etc.
     match value {
@@ -80,6 +407,32 @@ struct KindData {
     chmod: Option<u32>,
     suffix: String,
     content: String,
+    ttl: Option<String>,
+    default_name: Option<String>,
+    #[serde(default)]
+    open: bool,
+    #[serde(default)]
+    post_create: Vec<String>,
+    #[serde(default)]
+    requires: Requires,
+    #[serde(default)]
+    parents: bool,
+    dir_mode: Option<u32>,
+    dir: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    order: Option<i32>,
+}
+
+/// Expand a leading `~` to the home directory
+pub fn expand_tilde(path: &str) -> Result<PathBuf> {
+    if path == "~" || path.starts_with("~/") {
+        let home = std::env::var("HOME").map_err(|_| TmpError::HomeNotSet)?;
+        Ok(PathBuf::from(path.replacen('~', &home, 1)))
+    } else {
+        Ok(PathBuf::from(path))
+    }
 }
 
 pub fn load_config(path: &Path) -> Result<Config> {
@@ -87,20 +440,118 @@ pub fn load_config(path: &Path) -> Result<Config> {
 
     if !path.exists() {
         error!("Config file not found: {path:?}");
-        return Err(eyre::eyre!("Config file not found: {path:?}"));
+        return Err(TmpError::ConfigNotFound(path.to_path_buf()));
     }
 
     let content = fs::read_to_string(path).with_context(|| format!("Failed to read config file: {path:?}"))?;
 
     debug!("Config file content length: {len} bytes", len = content.len());
 
-    let config: Config =
-        serde_yaml::from_str(&content).with_context(|| format!("Failed to parse YAML config: {path:?}"))?;
+    let config: Config = serde_yaml::from_str(&content).map_err(|e| TmpError::config_parse(path, e))?;
 
     info!("Successfully loaded config from: {path:?}");
     Ok(config)
 }
 
+/// The nearest project config in `dir` or its ancestors
+pub fn find_project_config(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|ancestor| ancestor.join(PROJECT_CONFIG))
+        .find(|path| path.is_file())
+}
+
+/// The kinds and templates compiled into the binary
+pub fn builtin() -> Config {
+    serde_yaml::from_str::<Config>(STARTER_CONFIG)
+        .expect("built-in config is valid")
+        .with_origin(Origin::Builtin)
+}
+
+/// Load the built-in kinds, then the user config, then the nearest project config from `cwd`
+///
+/// Later layers replace kinds and templates of the same name; missing files, or no `user` path at
+/// all, are skipped. Kinds from a project config that `trust` doesn't vouch for are marked `untrusted`.
+pub fn load_layered(user: Option<&Path>, cwd: &Path, trust: Option<&Trust>) -> Result<Config> {
+    let mut config = builtin();
+    match user {
+        Some(user) if user.exists() => config.layer(load_config(user)?.with_origin(Origin::User)),
+        Some(user) => info!("No user config at {user:?}, using built-in kinds"),
+        None => info!("No user config, using built-in kinds"),
+    }
+    if let Some(project) = find_project_config(cwd) {
+        info!("Layering project config: {project:?}");
+        let mut layer = load_config(&project)?.with_origin(Origin::Project);
+        for kind in &layer.kinds {
+            if let Some(existing) = config.kinds.iter().find(|existing| existing.name == kind.name) {
+                warn!(
+                    "Project config {project:?} overrides {origin} kind '{name}'",
+                    origin = existing.origin,
+                    name = kind.name
+                );
+            }
+        }
+        // Relative directories belong to the project, wherever in it tmp runs
+        let root = project.parent().map(Path::to_path_buf);
+        for kind in &mut layer.kinds {
+            kind.root = root.clone();
+        }
+        layer.default_dir_root = root;
+        if !trust.is_some_and(|trust| trust.is_trusted(&project)) {
+            debug!("Project config {project:?} is not trusted to run commands");
+            for kind in &mut layer.kinds {
+                kind.untrusted = Some(project.clone());
+            }
+        }
+        config.layer(layer);
+    }
+    Ok(config)
+}
+
+/// The kinds the `tmp` command sees in `cwd`: built-ins, the default user config and the nearest project config
+///
+/// Project kinds only run commands once the project config is trusted with `tmp trust`. Without HOME
+/// the user config is skipped.
+pub fn load_default(cwd: &Path) -> Result<Config> {
+    let user = expand_tilde(DEFAULT_CONFIG).ok();
+    let trust = Trust::home().ok();
+    load_layered(user.as_deref(), cwd, trust.as_ref())
+}
+
+/// Where tmp keeps its registry, log and trusted configs: `~/.local/share/tmp`
+pub fn data_dir() -> Result<PathBuf> {
+    let home = std::env::var("HOME").map_err(|_| TmpError::HomeNotSet)?;
+    Ok(Path::new(&home).join(".local/share/tmp"))
+}
+
+/// Write a commented starter config, or the project one, to `path`
+///
+/// Fails with `TmpError::TargetExists` rather than overwriting an existing file.
+pub fn write_starter(path: &Path, project: bool) -> Result<()> {
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create config directory: {parent:?}"))?;
+    }
+
+    let mut file = match fs::OpenOptions::new().write(true).create_new(true).open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            return Err(TmpError::TargetExists(path.to_path_buf()));
+        }
+        Err(e) => return Err(e).with_context(|| format!("Failed to create config file: {path:?}")),
+    };
+    let content = if project {
+        STARTER_PROJECT_CONFIG
+    } else {
+        STARTER_CONFIG
+    };
+    std::io::Write::write_all(&mut file, content.as_bytes())
+        .with_context(|| format!("Failed to write config file: {path:?}"))?;
+
+    info!("Wrote starter config to {path:?}");
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -110,8 +561,7 @@ mod tests {
     #[test]
     fn test_load_config_file_not_found() {
         let result = load_config(Path::new("/nonexistent/path/config.yml"));
-        assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("Config file not found"));
+        assert!(matches!(result, Err(TmpError::ConfigNotFound(_))));
     }
 
     #[test]
@@ -121,8 +571,237 @@ mod tests {
         fs::write(&temp_file, "invalid: yaml: content: [").unwrap();
 
         let result = load_config(&temp_file);
-        assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("Failed to parse YAML"));
+        assert!(matches!(
+            result,
+            Err(TmpError::ConfigParse {
+                location: Some((1, _)),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_write_starter() {
+        let tempdir = tempdir().unwrap();
+        let path = tempdir.path().join(".config/tmp/tmp.yml");
+
+        write_starter(&path, false).unwrap();
+        let config = load_config(&path).unwrap();
+        for name in ["sh", "bash", "py", "rs", "md", "json", "yaml", "makefile"] {
+            let kind = config.kinds.iter().find(|k| k.name == name).unwrap();
+            assert!(config.expand_templates(&kind.content).is_ok());
+        }
+
+        // Never overwrites, even with the other starter
+        fs::write(&path, "kinds: {}\ntemplates: {}\n").unwrap();
+        assert!(matches!(write_starter(&path, true), Err(TmpError::TargetExists(_))));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "kinds: {}\ntemplates: {}\n");
+
+        let project = tempdir.path().join(PROJECT_CONFIG);
+        write_starter(&project, true).unwrap();
+        assert!(load_config(&project).unwrap().kinds.is_empty());
+    }
+
+    #[test]
+    fn test_load_layered() {
+        let tempdir = tempdir().unwrap();
+        let user = tempdir.path().join("tmp.yml");
+        let nested = tempdir.path().join("repo/src");
+        fs::create_dir_all(&nested).unwrap();
+
+        // Built-in kinds need no config at all
+        let config = load_layered(Some(&user), &nested, None).unwrap();
+        assert_eq!(config.kinds.len(), builtin().kinds.len());
+        assert!(config.kinds.iter().all(|k| k.origin == Origin::Builtin));
+
+        fs::write(
+            &user,
+            "kinds:\n  py:\n    suffix: py\n    content: user\n  sh:\n    suffix: sh\n    content: user\ntemplates:\n  a: user\n",
+        )
+        .unwrap();
+        fs::write(
+            tempdir.path().join("repo").join(PROJECT_CONFIG),
+            "kinds:\n  py:\n    suffix: py\n    content: project\ntemplates:\n  b: project\ndefault_dir: scratch\n",
+        )
+        .unwrap();
+
+        let config = load_layered(Some(&user), &nested, None).unwrap();
+        let kind = |name: &str| config.kinds.iter().find(|k| k.name == name).unwrap();
+        assert_eq!(kind("py").content, "project");
+        assert_eq!(kind("py").origin, Origin::Project);
+        assert_eq!(kind("sh").content, "user");
+        assert_eq!(kind("sh").origin, Origin::User);
+        assert_eq!(kind("md").origin, Origin::Builtin);
+        assert_eq!(config.kinds.len(), builtin().kinds.len());
+        assert!(config.templates.contains_key("a") && config.templates.contains_key("b"));
+        assert_eq!(config.default_dir.as_deref(), Some("scratch"));
+        let root = tempdir.path().join("repo");
+        assert_eq!(config.default_dir_root.as_deref(), Some(root.as_path()));
+        assert_eq!(kind("py").root.as_deref(), Some(root.as_path()));
+        assert_eq!(kind("sh").root, None);
+
+        // Only project kinds need trust, and only until the project config is trusted
+        let project = tempdir.path().join("repo").join(PROJECT_CONFIG);
+        assert_eq!(kind("py").untrusted.as_deref(), Some(project.as_path()));
+        assert_eq!(kind("sh").untrusted, None);
+        let trust = Trust::new(tempdir.path().join("trusted.yml"));
+        trust.add(&project).unwrap();
+        let config = load_layered(Some(&user), &nested, Some(&trust)).unwrap();
+        assert!(config.kinds.iter().all(|k| k.untrusted.is_none()));
+
+        // A project config alone layers over the built-ins
+        fs::remove_file(&user).unwrap();
+        let config = load_layered(Some(&user), &nested, None).unwrap();
+        let sh = config.kinds.iter().find(|k| k.name == "sh").unwrap();
+        assert_eq!(sh.origin, Origin::Builtin);
+    }
+
+    #[test]
+    fn test_load_default_includes_builtin_and_project_kinds() {
+        let tempdir = tempdir().unwrap();
+        fs::write(
+            tempdir.path().join(PROJECT_CONFIG),
+            "templates: {}\nkinds:\n  fixture:\n    suffix: json\n    content: ''\n",
+        )
+        .unwrap();
+
+        let config = load_default(tempdir.path()).unwrap();
+        let origin = |name: &str| config.kinds.iter().find(|k| k.name == name).map(|k| k.origin);
+        assert_eq!(origin("fixture"), Some(Origin::Project));
+        assert!(origin("md").is_some());
+    }
+
+    #[test]
+    fn test_layer_orders_kinds_by_layer() {
+        let user = Config {
+            kinds: vec![Kind::new("mine"), Kind::new("sh"), Kind::new("py")],
+            ..Default::default()
+        }
+        .with_origin(Origin::User);
+
+        let mut config = builtin();
+        config.layer(user);
+        let names: Vec<&str> = config.kinds.iter().map(|k| k.name.as_str()).collect();
+        let builtins: Vec<String> = builtin()
+            .kinds
+            .into_iter()
+            .map(|k| k.name)
+            .filter(|name| name != "sh" && name != "py")
+            .collect();
+        assert_eq!(names[..3], ["mine", "sh", "py"]);
+        assert_eq!(names[3..], builtins);
+        assert!(config.kinds[..3].iter().all(|k| k.origin == Origin::User));
+        assert!(config.kinds[3..].iter().all(|k| k.origin == Origin::Builtin));
+    }
+
+    #[test]
+    fn test_tags_filter_and_group() {
+        let yaml = "templates: {}\nkinds:\n  py:\n    suffix: py\n    content: ''\n    tags: [python, script]\n  pytest:\n    suffix: py\n    content: ''\n    tags: [python, test]\n  txt:\n    suffix: txt\n    content: ''\n";
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let kind = |name: &str| config.kinds.iter().find(|k| k.name == name).unwrap();
+        assert_eq!(kind("pytest").tags, vec!["python", "test"]);
+        assert!(kind("txt").tags.is_empty());
+
+        let names = |filter: &KindFilter| {
+            let mut names: Vec<&str> = config
+                .kinds
+                .iter()
+                .filter(|k| filter.matches(k))
+                .map(|k| k.name.as_str())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names(&KindFilter::default()), vec!["py", "pytest", "txt"]);
+        let python = KindFilter {
+            tags: vec!["python".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(names(&python), vec!["py", "pytest"]);
+        let python_tests = KindFilter {
+            tags: vec!["python".to_string(), "test".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(names(&python_tests), vec!["pytest"]);
+        let txt = KindFilter {
+            suffix: Some(".txt".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(names(&txt), vec!["txt"]);
+
+        let kinds = [kind("py"), kind("pytest"), kind("txt")];
+        let groups: Vec<(Option<&str>, Vec<&str>)> = group_by_tag(&kinds)
+            .into_iter()
+            .map(|(tag, kinds)| (tag, kinds.iter().map(|k| k.name.as_str()).collect()))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                (Some("python"), vec!["py", "pytest"]),
+                (Some("script"), vec!["py"]),
+                (Some("test"), vec!["pytest"]),
+                (None, vec!["txt"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_kinds_keep_declared_order() {
+        let names = ["zeta", "alpha", "mid", "beta", "omega", "gamma", "delta", "kappa"];
+        let yaml: String = names
+            .iter()
+            .map(|name| format!("  {name}:\n    suffix: txt\n    content: ''\n"))
+            .collect();
+        let yaml = format!("templates: {{}}\nkinds:\n{yaml}");
+
+        for _ in 0..5 {
+            let config: Config = serde_yaml::from_str(&yaml).unwrap();
+            let loaded: Vec<&str> = config.kinds.iter().map(|k| k.name.as_str()).collect();
+            assert_eq!(loaded, names);
+        }
+
+        let mut config: Config = serde_yaml::from_str(&yaml).unwrap();
+        let overlay: Config = serde_yaml::from_str(
+            "templates: {}\nkinds:\n  new:\n    suffix: md\n    content: ''\n  mid:\n    suffix: md\n    content: ''\n",
+        )
+        .unwrap();
+        config.layer(overlay);
+        let layered: Vec<&str> = config.kinds.iter().map(|k| k.name.as_str()).collect();
+        // The overlay's kinds lead in its order, then the ones it leaves alone in theirs
+        assert_eq!(
+            layered,
+            [
+                "new", "mid", "zeta", "alpha", "beta", "omega", "gamma", "delta", "kappa"
+            ]
+        );
+        assert_eq!(config.kinds[1].suffix, "md");
+
+        let duplicate =
+            "templates: {}\nkinds:\n  a:\n    suffix: txt\n    content: ''\n  a:\n    suffix: md\n    content: ''\n";
+        let error = serde_yaml::from_str::<Config>(duplicate).unwrap_err();
+        assert!(error.to_string().contains("kind 'a' is defined twice"), "{error}");
+    }
+
+    #[test]
+    fn test_expand_templates() {
+        let yaml = "kinds: {}\ntemplates:\n  header: \"#!/bin/bash\\n{strict}\"\n  strict: set -eu";
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            config.expand_templates("{header}\necho {name}\n").unwrap(),
+            "#!/bin/bash\nset -eu\necho {name}\n"
+        );
+
+        let yaml = "kinds: {}\ntemplates:\n  a: \"{b}\"\n  b: \"{a}\"";
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let Err(TmpError::TemplateCycle(cycle)) = config.expand_templates("{a}") else {
+            panic!("expected a template cycle");
+        };
+        assert_eq!(cycle, vec!["a", "b", "a"]);
+
+        // Escaped placeholders are kept, and expanded text is never expanded again
+        let yaml = "kinds: {}\ntemplates:\n  a: \"{{b} {c}\"\n  b: B\n  c: \"{{b}\"";
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.expand_templates("{a} {{a} {b}").unwrap(), "{b} {b} {a} B");
     }
 
     #[test]
@@ -193,4 +872,96 @@ mod tests {
         // Test invalid octal digits (should remain unchanged)
         assert_eq!(interpret_chmod_value(789), 789); // Contains 8,9 - not valid octal
     }
+
+    #[test]
+    fn test_kind_ttl() {
+        let yaml = "kinds:\n  scratch:\n    suffix: txt\n    ttl: 2h\n    content: x\n  keep:\n    suffix: txt\n    content: y\ntemplates: {}";
+
+        let config: Config = serde_yaml::from_str(yaml).expect("Failed to parse config");
+
+        let scratch = config.kinds.iter().find(|k| k.name == "scratch").unwrap();
+        assert_eq!(scratch.ttl, Some(Duration::from_secs(2 * 60 * 60)));
+
+        let keep = config.kinds.iter().find(|k| k.name == "keep").unwrap();
+        assert_eq!(keep.ttl, None);
+
+        let invalid = "kinds:\n  bad:\n    suffix: txt\n    ttl: soon\n    content: x\ntemplates: {}";
+        let err = serde_yaml::from_str::<Config>(invalid).unwrap_err();
+        assert!(err.to_string().contains("kind 'bad'"));
+    }
+
+    #[test]
+    fn test_kind_post_create() {
+        let yaml = "kinds:\n  rs:\n    suffix: rs\n    content: x\n    post_create:\n      - git add {path}\n      - cargo fmt\ntemplates: {}";
+
+        let config: Config = serde_yaml::from_str(yaml).expect("Failed to parse config");
+
+        assert_eq!(config.kinds[0].post_create, vec!["git add {path}", "cargo fmt"]);
+    }
+
+    #[test]
+    fn test_kind_parents_and_dir_mode() {
+        let yaml =
+            "kinds:\n  test:\n    suffix: py\n    content: x\n    parents: true\n    dir_mode: 750\ntemplates: {}";
+
+        let config: Config = serde_yaml::from_str(yaml).expect("Failed to parse config");
+
+        assert!(config.kinds[0].parents);
+        assert_eq!(config.kinds[0].dir_mode, Some(0o750));
+    }
+
+    #[test]
+    fn test_dirs() {
+        let yaml = "default_dir: ~/tmp/scratch\nkinds:\n  test:\n    suffix: py\n    content: x\n    dir: tests/{kind}\ntemplates: {}";
+
+        let config: Config = serde_yaml::from_str(yaml).expect("Failed to parse config");
+
+        assert_eq!(config.default_dir.as_deref(), Some("~/tmp/scratch"));
+        assert_eq!(config.kinds[0].dir.as_deref(), Some("tests/{kind}"));
+    }
+
+    #[test]
+    fn test_expand_tilde() {
+        let home = std::env::var("HOME").unwrap();
+        assert_eq!(expand_tilde("~/x").unwrap(), Path::new(&home).join("x"));
+        assert_eq!(expand_tilde("~").unwrap(), PathBuf::from(&home));
+        assert_eq!(expand_tilde("/abs/~/x").unwrap(), PathBuf::from("/abs/~/x"));
+        assert_eq!(expand_tilde("~other/x").unwrap(), PathBuf::from("~other/x"));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(30 * 60));
+        assert_eq!(parse_duration("1h30m").unwrap(), Duration::from_secs(90 * 60));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(7 * 24 * 60 * 60));
+        assert_eq!(parse_duration("1w").unwrap(), Duration::from_secs(7 * 24 * 60 * 60));
+
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("2").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("2y").is_err());
+        assert!(matches!(
+            parse_duration("999999999999999d"),
+            Err(TmpError::InvalidDuration { reason, .. }) if reason == "too long"
+        ));
+        assert!(parse_duration("99999999999999999999999s").is_err());
+    }
+
+    #[test]
+    fn test_kind_filename() {
+        let kind = Kind {
+            name: "py".to_string(),
+            suffix: "py".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(kind.filename("script"), "script.py");
+        assert_eq!(kind.filename("script.py"), "script.py");
+
+        let bare = Kind {
+            name: "makefile".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(bare.filename("Makefile"), "Makefile");
+    }
 }
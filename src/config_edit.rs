@@ -0,0 +1,447 @@
+//! Changes to config files that keep their comments and layout
+//!
+//! serde_yaml drops comments when it writes a document, so kinds are added by splicing a
+//! serialized block into the file's text instead of rewriting the whole file.
+
+use crate::config::{Config, Kind, interpret_chmod_value};
+use crate::editor::CURSOR_MARKER;
+use crate::error::{Context, Result, TmpError};
+use crate::vars::{Replacement, Vars};
+use log::{debug, info};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+/// Built-in variables that `templatize` looks for in a source file
+const TEMPLATIZED: &[&str] = &["name", "date"];
+
+/// What a new file gets before its first kind
+const NEW_CONFIG: &str = "templates: {}\n\nkinds:\n";
+
+/// The keys `add_kind` writes, in the order they appear in the file
+#[derive(Serialize)]
+struct KindEntry<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    suffix: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chmod: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default_name: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    tags: &'a [String],
+    content: &'a str,
+}
+
+/// A kind copying `path`: its extension becomes the suffix and its mode the chmod
+///
+/// Files without an extension, such as `Makefile`, keep their name as the kind's `default_name`.
+/// Anything tmp would substitute, such as `{tmp.name}`, `{cursor}` or one of `templates`, is escaped so
+/// new files get it as written; those placeholders are returned alongside the kind.
+pub fn kind_from_file(path: &Path, name: &str, templates: &HashMap<String, String>) -> Result<(Kind, Vec<String>)> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {path:?}"))?;
+    let mode = fs::metadata(path)
+        .with_context(|| format!("Failed to read metadata of {path:?}"))?
+        .permissions()
+        .mode();
+
+    let suffix = path.extension().map(|ext| ext.to_string_lossy().to_string());
+    let default_name = match suffix {
+        Some(_) => None,
+        None => path.file_name().map(|name| name.to_string_lossy().to_string()),
+    };
+
+    let (content, escaped) = escape_placeholders(&content, templates);
+    if !escaped.is_empty() {
+        debug!("Escaped placeholders in {path:?}: {escaped:?}");
+    }
+
+    let kind = Kind {
+        name: name.to_string(),
+        suffix: suffix.unwrap_or_default(),
+        chmod: Some(mode & 0o777),
+        content,
+        default_name,
+        ..Default::default()
+    };
+    Ok((kind, escaped))
+}
+
+/// Double the opening brace of every placeholder in `content` that would be substituted
+fn escape_placeholders(content: &str, templates: &HashMap<String, String>) -> (String, Vec<String>) {
+    let mut escaped = String::with_capacity(content.len());
+    let mut found: Vec<String> = Vec::new();
+    for (index, piece) in content.split_inclusive('{').enumerate() {
+        // Every piece but the first follows a `{`
+        if index > 0
+            && let Some(end) = piece.find('}')
+        {
+            let placeholder = &piece[..end];
+            let token = format!("{{{placeholder}}}");
+            if placeholder.starts_with("tmp.") || token == CURSOR_MARKER || templates.contains_key(placeholder) {
+                escaped.push('{');
+                if !found.contains(&token) {
+                    found.push(token);
+                }
+            }
+        }
+        escaped.push_str(piece);
+    }
+    (escaped, found)
+}
+
+/// Turn values from `source` back into placeholders in `kind`'s content, returning the replacements
+///
+/// The file's `{tmp.name}`, today's `{tmp.date}` and every variable in `vars`, such as `author`, are
+/// replaced wherever they appear as whole words. The file's `{tmp.stem}` is only replaced with `stem`,
+/// since it's often also a name in the code, like `fetch()` in fetch.py.
+pub fn templatize(kind: &mut Kind, source: &Path, vars: &Vars, stem: bool) -> Vec<Replacement> {
+    let builtins = Vars::for_file(&kind.name, &kind.suffix, source);
+    let mut all: Vars = builtins
+        .iter()
+        .filter(|(key, _)| TEMPLATIZED.contains(key) || (stem && *key == "stem"))
+        .collect();
+    all.extend(vars);
+
+    let (content, replacements) = all.templatize(&kind.content);
+    debug!("Templatized {source:?}: {replacements:?}");
+    kind.content = content;
+    replacements
+}
+
+/// Add `kind` to the end of the `kinds:` map in the config at `path`, creating the file if needed
+///
+/// Fails with `TmpError::KindExists` if the file already defines a kind with that name.
+pub fn add_kind(path: &Path, kind: &Kind) -> Result<()> {
+    let text = if path.exists() {
+        fs::read_to_string(path).with_context(|| format!("Failed to read config file: {path:?}"))?
+    } else {
+        String::new()
+    };
+
+    if !text.trim().is_empty() {
+        let config: Config = serde_yaml::from_str(&text).map_err(|e| TmpError::config_parse(path, e))?;
+        if config.kinds.iter().any(|k| k.name == kind.name) {
+            return Err(TmpError::KindExists {
+                name: kind.name.clone(),
+                path: path.to_path_buf(),
+            });
+        }
+    }
+
+    let updated = insert_kind(&text, &render_kind(kind)?).map_err(|line| TmpError::ConfigParse {
+        path: path.to_path_buf(),
+        location: Some((line, 1)),
+        message: "`kinds:` must be a block mapping to add kinds to it".to_string(),
+    })?;
+    // Never leave a config behind that tmp can't read
+    serde_yaml::from_str::<Config>(&updated).map_err(|e| TmpError::config_parse(path, e))?;
+
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create config directory: {parent:?}"))?;
+    }
+    fs::write(path, updated).with_context(|| format!("Failed to write config file: {path:?}"))?;
+
+    info!("Added kind {name} to {path:?}", name = kind.name);
+    Ok(())
+}
+
+/// The 1-based line of `name`'s key in the top-level `kinds:` map of `text`
+pub fn kind_line(text: &str, name: &str) -> Option<usize> {
+    let lines: Vec<&str> = text.lines().collect();
+    let start = lines.iter().position(|line| is_kinds_key(line))?;
+    let end = block_end(&lines, start);
+    let indent = child_indent(&lines[start + 1..end])?;
+
+    (start + 1..end)
+        .find(|&index| {
+            let line = lines[index];
+            indent_of(line) == indent
+                && line
+                    .trim_start()
+                    .split_once(':')
+                    .is_some_and(|(key, _)| key.trim().trim_matches(|c| c == '"' || c == '\'') == name)
+        })
+        .map(|index| index + 1)
+}
+
+/// `kind` as a one-entry YAML map, unindented
+fn render_kind(kind: &Kind) -> Result<String> {
+    let entry = KindEntry {
+        description: kind.description.as_deref(),
+        suffix: &kind.suffix,
+        chmod: kind.chmod.map(chmod_value),
+        default_name: kind.default_name.as_deref(),
+        tags: &kind.tags,
+        content: &kind.content,
+    };
+    let map = BTreeMap::from([(kind.name.as_str(), entry)]);
+    serde_yaml::to_string(&map).context("Failed to serialize kind")
+}
+
+/// `mode` the way people write it in the config, e.g. 0o755 as `755`, when it reads back the same
+fn chmod_value(mode: u32) -> u32 {
+    format!("{mode:o}")
+        .parse()
+        .ok()
+        .filter(|&written| interpret_chmod_value(written) == mode)
+        .unwrap_or(mode)
+}
+
+/// Splice the rendered kind `block` into `text`, or the 1-based line of a `kinds:` that can't take it
+fn insert_kind(text: &str, block: &str) -> std::result::Result<String, usize> {
+    if text.trim().is_empty() {
+        return Ok(format!("{NEW_CONFIG}{}", indent_block(block, 2)));
+    }
+
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    let Some(start) = lines.iter().position(|line| is_kinds_key(line)) else {
+        let mut text = text.to_string();
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        return Ok(format!("{text}\nkinds:\n{}", indent_block(block, 2)));
+    };
+
+    match value_of(&lines[start]) {
+        "" => {}
+        "{}" => lines[start] = "kinds:".to_string(),
+        _ => return Err(start + 1),
+    }
+
+    let end = block_end(&lines, start);
+    let children = &lines[start + 1..end];
+    let indent = child_indent(children).unwrap_or(2);
+    // Follow the file's layout: a blank line between kinds if it already uses them
+    let separator = child_indent(children).is_some() && children.iter().any(|line| line.trim().is_empty());
+    let insert_at = (start + 1..end)
+        .rev()
+        .find(|&index| !lines[index].trim().is_empty())
+        .map_or(start + 1, |index| index + 1);
+    debug!(
+        "Inserting kind at line {line} with indent {indent}",
+        line = insert_at + 1
+    );
+
+    let mut new_lines: Vec<String> = Vec::new();
+    if separator {
+        new_lines.push(String::new());
+    }
+    new_lines.extend(indent_block(block, indent).lines().map(str::to_string));
+    lines.splice(insert_at..insert_at, new_lines);
+
+    Ok(lines.join("\n") + "\n")
+}
+
+fn is_kinds_key(line: &str) -> bool {
+    line.strip_prefix("kinds")
+        .is_some_and(|rest| rest.trim_start().starts_with(':'))
+}
+
+/// What follows the key on a `key: value` line, without a trailing comment
+fn value_of(line: &str) -> &str {
+    let value = line.split_once(':').map_or("", |(_, value)| value);
+    value.split(" #").next().unwrap_or_default().trim()
+}
+
+/// Index just past the lines belonging to the top-level key at `start`
+fn block_end<S: AsRef<str>>(lines: &[S], start: usize) -> usize {
+    (start + 1..lines.len())
+        .find(|&index| {
+            let line = lines[index].as_ref();
+            !line.trim().is_empty() && indent_of(line) == 0
+        })
+        .unwrap_or(lines.len())
+}
+
+/// Indentation of the first entry in a block, ignoring blank lines and comments
+fn child_indent<S: AsRef<str>>(lines: &[S]) -> Option<usize> {
+    lines
+        .iter()
+        .map(AsRef::as_ref)
+        .find(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(indent_of)
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn indent_block(block: &str, indent: usize) -> String {
+    block
+        .lines()
+        .map(|line| {
+            if line.is_empty() {
+                "\n".to_string()
+            } else {
+                format!("{:indent$}{line}\n", "")
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::load_config;
+    use crate::{CreateOptions, Tmp};
+    use tempfile::tempdir;
+
+    fn example(name: &str, content: &str) -> Kind {
+        Kind {
+            name: name.to_string(),
+            suffix: "py".to_string(),
+            chmod: Some(0o755),
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_add_kind_keeps_comments() {
+        let tempdir = tempdir().unwrap();
+        let path = tempdir.path().join("tmp.yml");
+        let original = "# my kinds\ntemplates:\n  hdr: \"#!/bin/sh\"\n\nkinds:\n  # shell first\n  sh:\n    suffix: sh\n    content: |\n      {hdr}\n\n  txt:\n    suffix: txt\n    content: \"\"\n\n# trailing note\ndefault_dir: ~/tmp\n";
+        fs::write(&path, original).unwrap();
+
+        add_kind(&path, &example("pyex", "def main():\n\n    pass\n")).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+
+        assert!(text.starts_with(&original[..original.find("\n# trailing note").unwrap()]));
+        assert!(text.contains("# shell first\n"));
+        assert!(text.ends_with("\n# trailing note\ndefault_dir: ~/tmp\n"));
+        assert!(text.contains("\n\n  pyex:\n    suffix: py\n    chmod: 755\n"));
+
+        let config = load_config(&path).unwrap();
+        let kind = config.kinds.iter().find(|k| k.name == "pyex").unwrap();
+        assert_eq!(kind.content, "def main():\n\n    pass\n");
+        assert_eq!(kind.chmod, Some(0o755));
+        assert_eq!(config.default_dir.as_deref(), Some("~/tmp"));
+        assert_eq!(
+            kind_line(&text, "pyex"),
+            text.lines().position(|l| l == "  pyex:").map(|i| i + 1)
+        );
+
+        assert!(matches!(
+            add_kind(&path, &example("pyex", "")),
+            Err(TmpError::KindExists { .. })
+        ));
+    }
+
+    #[test]
+    fn test_add_kind_to_new_and_empty_configs() {
+        let tempdir = tempdir().unwrap();
+
+        let path = tempdir.path().join("new/tmp.yml");
+        add_kind(&path, &example("a", "  indented\nno newline")).unwrap();
+        let config = load_config(&path).unwrap();
+        assert_eq!(config.kinds[0].content, "  indented\nno newline");
+
+        let path = tempdir.path().join(".tmp.yml");
+        fs::write(&path, "templates: {}\n\nkinds: {}\n  # example:\n  #   suffix: py\n").unwrap();
+        add_kind(&path, &example("b", "x\n")).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.contains("kinds:\n  # example:\n  #   suffix: py\n  b:\n"));
+        assert_eq!(load_config(&path).unwrap().kinds.len(), 1);
+
+        fs::write(&path, "templates: {}\nkinds: {b: {suffix: py, content: x}}\n").unwrap();
+        assert!(matches!(
+            add_kind(&path, &example("c", "")),
+            Err(TmpError::ConfigParse {
+                location: Some((2, 1)),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_kind_from_file() {
+        let tempdir = tempdir().unwrap();
+        let script = tempdir.path().join("example.py");
+        fs::write(&script, "print('hi')\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o750)).unwrap();
+
+        let (kind, escaped) = kind_from_file(&script, "pyex", &HashMap::new()).unwrap();
+        assert_eq!(kind.suffix, "py");
+        assert_eq!(kind.chmod, Some(0o750));
+        assert_eq!(kind.content, "print('hi')\n");
+        assert_eq!(kind.default_name, None);
+        assert!(escaped.is_empty());
+
+        let makefile = tempdir.path().join("Makefile");
+        fs::write(&makefile, "all:\n").unwrap();
+        let (kind, _) = kind_from_file(&makefile, "make", &HashMap::new()).unwrap();
+        assert_eq!(kind.suffix, "");
+        assert_eq!(kind.default_name.as_deref(), Some("Makefile"));
+    }
+
+    #[test]
+    fn test_kind_from_file_escapes_placeholders() {
+        let tempdir = tempdir().unwrap();
+        let source = tempdir.path().join("gen.sh");
+        let text = "#!/bin/sh\n{sh-header}\n# {tmp.name} at {cursor}, {{tmp.x} {foo} ${HOME}\n";
+        fs::write(&source, text).unwrap();
+
+        let templates = HashMap::from([("sh-header".to_string(), "set -eu".to_string())]);
+        let (kind, escaped) = kind_from_file(&source, "gen", &templates).unwrap();
+        assert_eq!(escaped, vec!["{sh-header}", "{tmp.name}", "{cursor}", "{tmp.x}"]);
+
+        // New files get the source exactly as it was
+        let config = Config {
+            kinds: vec![kind],
+            templates,
+            ..Default::default()
+        };
+        let tmp = Tmp::new(config).unwrap();
+        let content = tmp.render_kind("gen", Some("out"), &CreateOptions::default()).unwrap();
+        assert_eq!(content, text);
+    }
+
+    #[test]
+    fn test_templatize() {
+        let tempdir = tempdir().unwrap();
+        let source = tempdir.path().join("fetch.py");
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        fs::write(
+            &source,
+            format!("# fetch.py by Ada, {today}\nimport fetcher\nfetch()\n"),
+        )
+        .unwrap();
+
+        let (original, _) = kind_from_file(&source, "pyex", &HashMap::new()).unwrap();
+        let vars = Vars::from_iter([("author", "Ada")]);
+
+        // The stem is left alone unless asked for
+        let mut kind = original.clone();
+        let replacements = templatize(&mut kind, &source, &vars, false);
+        assert_eq!(
+            kind.content,
+            "# {tmp.name} by {tmp.author}, {tmp.date}\nimport fetcher\nfetch()\n"
+        );
+        let keys: Vec<&str> = replacements.iter().map(|r| r.key.as_str()).collect();
+        assert_eq!(keys, vec!["name", "author", "date"]);
+
+        let mut kind = original;
+        let replacements = templatize(&mut kind, &source, &vars, true);
+        assert_eq!(
+            kind.content,
+            "# {tmp.name} by {tmp.author}, {tmp.date}\nimport fetcher\n{tmp.stem}()\n"
+        );
+        assert_eq!(
+            replacements.last().map(|r| (r.line, r.value.as_str())),
+            Some((3, "fetch"))
+        );
+    }
+
+    #[test]
+    fn test_chmod_value() {
+        assert_eq!(chmod_value(0o755), 755);
+        assert_eq!(chmod_value(0o640), 640);
+        // 400 would be read as decimal, so the raw mode is written instead
+        assert_eq!(chmod_value(0o400), 0o400);
+    }
+}
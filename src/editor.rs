@@ -0,0 +1,126 @@
+use crate::error::{Context, Result, TmpError};
+use log::{debug, info};
+use std::path::Path;
+use std::process::Command;
+
+/// Placeholder marking where the editor cursor should start; never written to disk
+pub const CURSOR_MARKER: &str = "{cursor}";
+
+/// Editors known to accept `+LINE` before the file to open
+const PLUS_LINE_EDITORS: &[&str] = &[
+    "vi",
+    "vim",
+    "nvim",
+    "gvim",
+    "view",
+    "nano",
+    "pico",
+    "emacs",
+    "emacsclient",
+    "micro",
+    "kak",
+    "joe",
+    "jed",
+    "ne",
+    "mg",
+];
+
+/// Split `content` at the first cursor marker, returning the text before it and, if found, after it
+///
+/// `{{cursor}` is not a marker; it stands for a literal `{cursor}`.
+pub fn split_cursor(content: &str) -> (String, Option<String>) {
+    let escaped = format!("{{{CURSOR_MARKER}");
+    let unescape = |text: &str| text.replace(&escaped, CURSOR_MARKER);
+    let marker = content
+        .match_indices(CURSOR_MARKER)
+        .map(|(index, _)| index)
+        .find(|&index| !content[..index].ends_with('{'));
+    match marker {
+        Some(index) => (
+            unescape(&content[..index]),
+            Some(unescape(&content[index + CURSOR_MARKER.len()..])),
+        ),
+        None => (unescape(content), None),
+    }
+}
+
+/// Open `path` in `$VISUAL` or `$EDITOR`, placing the cursor on `line` when the editor supports it
+pub fn open(path: &Path, line: Option<usize>) -> Result<()> {
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|value| !value.trim().is_empty()))
+        .ok_or_else(|| TmpError::Editor("Set $VISUAL or $EDITOR to open files with --edit".to_string()))?;
+
+    let args = command_args(&editor, path, line);
+    debug!("Running editor: {args:?}");
+
+    let (program, rest) = args.split_first().expect("editor command is never empty");
+    let status = Command::new(program)
+        .args(rest)
+        .status()
+        .with_context(|| format!("Failed to launch editor: {program}"))?;
+
+    if !status.success() {
+        return Err(TmpError::Editor(format!("Editor {program} exited with {status}")));
+    }
+
+    info!("Edited {path:?} with {program}");
+    Ok(())
+}
+
+fn command_args(editor: &str, path: &Path, line: Option<usize>) -> Vec<String> {
+    let mut args: Vec<String> = editor.split_whitespace().map(str::to_string).collect();
+
+    let program = Path::new(&args[0])
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if let Some(line) = line
+        && PLUS_LINE_EDITORS.contains(&program.as_str())
+    {
+        args.push(format!("+{line}"));
+    }
+
+    args.push(path.to_string_lossy().to_string());
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_cursor() {
+        let (before, after) = split_cursor("#!/bin/bash\n\n{cursor}\nexit 0\n");
+        assert_eq!(before, "#!/bin/bash\n\n");
+        assert_eq!(after.as_deref(), Some("\nexit 0\n"));
+
+        let (before, after) = split_cursor("def main():\n    {cursor}pass {cursor}\n");
+        assert_eq!(before, "def main():\n    ");
+        assert_eq!(after.as_deref(), Some("pass {cursor}\n"));
+
+        let (before, after) = split_cursor("no marker\n");
+        assert_eq!(before, "no marker\n");
+        assert_eq!(after, None);
+
+        let (before, after) = split_cursor("keep {{cursor} here {cursor}");
+        assert_eq!(before, "keep {cursor} here ");
+        assert_eq!(after.as_deref(), Some(""));
+    }
+
+    #[test]
+    fn test_command_args() {
+        let path = Path::new("/tmp/foo.py");
+
+        assert_eq!(command_args("nvim", path, Some(4)), vec!["nvim", "+4", "/tmp/foo.py"]);
+        assert_eq!(
+            command_args("/usr/bin/emacsclient -t", path, Some(2)),
+            vec!["/usr/bin/emacsclient", "-t", "+2", "/tmp/foo.py"]
+        );
+        assert_eq!(
+            command_args("code --wait", path, Some(4)),
+            vec!["code", "--wait", "/tmp/foo.py"]
+        );
+        assert_eq!(command_args("vim", path, None), vec!["vim", "/tmp/foo.py"]);
+    }
+}
@@ -0,0 +1,240 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use thiserror::Error;
+
+/// Exit codes returned by the tmp binary
+pub mod exit {
+    pub const FAILURE: u8 = 1;
+    pub const UNKNOWN_KIND: u8 = 3;
+    pub const CONFLICT: u8 = 4;
+    pub const IO: u8 = 5;
+    pub const CONFIG: u8 = 6;
+    pub const REQUIREMENTS: u8 = 7;
+    pub const PERMISSION_DENIED: u8 = 8;
+}
+
+/// Result type returned throughout the library
+pub type Result<T, E = TmpError> = std::result::Result<T, E>;
+
+/// Failures that callers (and scripts, via the exit code) need to tell apart
+#[derive(Debug, Error)]
+pub enum TmpError {
+    #[error("Kind '{0}' not found")]
+    UnknownKind(String),
+
+    #[error("File {0:?} already exists")]
+    TargetExists(PathBuf),
+
+    #[error("Kind '{name}' is already defined in {path:?}")]
+    KindExists { name: String, path: PathBuf },
+
+    #[error("Config file not found: {0:?} (run `tmp init` to create one)")]
+    ConfigNotFound(PathBuf),
+
+    #[error("Failed to parse {path:?}{}: {message}", location.map(|(line, column)| format!(" at line {line}, column {column}")).unwrap_or_default())]
+    ConfigParse {
+        path: PathBuf,
+        /// 1-based line and column of the error, when known
+        location: Option<(usize, usize)>,
+        message: String,
+    },
+
+    #[error("Template cycle: {}", .0.join(" -> "))]
+    TemplateCycle(Vec<String>),
+
+    #[error("Unresolved placeholder {{{placeholder}}} in {context}")]
+    UnresolvedPlaceholder { placeholder: String, context: String },
+
+    #[error("Kind '{kind}' can't be created here:\n{}", reasons.iter().map(|r| format!("  - {r}")).collect::<Vec<_>>().join("\n"))]
+    RequirementsNotMet { kind: String, reasons: Vec<String> },
+
+    #[error("Directory {0:?} does not exist (use --parents to create it)")]
+    MissingParent(PathBuf),
+
+    #[error("Trashed file no longer exists: {0:?}")]
+    NotInTrash(PathBuf),
+
+    #[error("Cannot trash {0:?}: it has no file name")]
+    NotTrashable(PathBuf),
+
+    #[error(
+        "Kind '{kind}' comes from {config:?}, which isn't trusted to run commands; review it, then run `tmp trust`"
+    )]
+    Untrusted { kind: String, config: PathBuf },
+
+    #[error("Refusing to delete {0:?}: it was modified or not created by tmp (use --force to delete anyway)")]
+    Refused(PathBuf),
+
+    #[error(
+        "Created {path:?}, but post_create hook {index}/{total} `{command}` failed with {status}; remaining hooks were skipped"
+    )]
+    HookFailed {
+        path: PathBuf,
+        index: usize,
+        total: usize,
+        command: String,
+        status: ExitStatus,
+    },
+
+    #[error("{0}")]
+    Editor(String),
+
+    #[error("Invalid duration '{value}': {reason}")]
+    InvalidDuration { value: String, reason: String },
+
+    #[error("HOME environment variable not set")]
+    HomeNotSet,
+
+    #[error("{context}: permission denied")]
+    PermissionDenied {
+        context: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("{context}")]
+    Io {
+        context: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("{context}")]
+    Yaml {
+        context: String,
+        #[source]
+        source: serde_yaml::Error,
+    },
+}
+
+impl TmpError {
+    /// The file that was written before the error, and is still there
+    pub fn created_path(&self) -> Option<&Path> {
+        match self {
+            Self::HookFailed { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::UnknownKind(_) => exit::UNKNOWN_KIND,
+            Self::TargetExists(_) | Self::KindExists { .. } => exit::CONFLICT,
+            Self::ConfigNotFound(_)
+            | Self::ConfigParse { .. }
+            | Self::TemplateCycle(_)
+            | Self::UnresolvedPlaceholder { .. }
+            | Self::Yaml { .. } => exit::CONFIG,
+            Self::RequirementsNotMet { .. } => exit::REQUIREMENTS,
+            Self::PermissionDenied { .. } => exit::PERMISSION_DENIED,
+            Self::Io { .. } | Self::MissingParent(_) | Self::NotInTrash(_) => exit::IO,
+            Self::Refused(_)
+            | Self::NotTrashable(_)
+            | Self::Untrusted { .. }
+            | Self::HookFailed { .. }
+            | Self::Editor(_)
+            | Self::InvalidDuration { .. }
+            | Self::HomeNotSet => exit::FAILURE,
+        }
+    }
+}
+
+impl TmpError {
+    /// A parse error for the YAML file at `path`, keeping the position serde_yaml reports
+    pub(crate) fn config_parse(path: &std::path::Path, source: serde_yaml::Error) -> Self {
+        let location = source.location().map(|location| (location.line(), location.column()));
+        let message = source.to_string();
+        // serde_yaml appends the location to its message; it's shown separately here
+        let message = match location {
+            Some((line, column)) => message
+                .strip_suffix(&format!(" at line {line} column {column}"))
+                .map(str::to_string)
+                .unwrap_or(message),
+            None => message,
+        };
+
+        Self::ConfigParse {
+            path: path.to_path_buf(),
+            location,
+            message,
+        }
+    }
+}
+
+/// Attach a description of what was being attempted to an I/O or YAML error
+pub(crate) trait Context<T> {
+    fn with_context<F: FnOnce() -> String>(self, context: F) -> Result<T>;
+
+    fn context(self, context: &str) -> Result<T>
+    where
+        Self: Sized,
+    {
+        self.with_context(|| context.to_string())
+    }
+}
+
+impl<T> Context<T> for std::result::Result<T, std::io::Error> {
+    fn with_context<F: FnOnce() -> String>(self, context: F) -> Result<T> {
+        self.map_err(|source| match source.kind() {
+            std::io::ErrorKind::PermissionDenied => TmpError::PermissionDenied {
+                context: context(),
+                source,
+            },
+            _ => TmpError::Io {
+                context: context(),
+                source,
+            },
+        })
+    }
+}
+
+impl<T> Context<T> for std::result::Result<T, serde_yaml::Error> {
+    fn with_context<F: FnOnce() -> String>(self, context: F) -> Result<T> {
+        self.map_err(|source| TmpError::Yaml {
+            context: context(),
+            source,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_wraps_source() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "gone");
+        let err = Err::<(), _>(io).context("Failed to write").unwrap_err();
+        assert!(matches!(err, TmpError::Io { .. }));
+        assert_eq!(err.to_string(), "Failed to write");
+        assert_eq!(std::error::Error::source(&err).unwrap().to_string(), "gone");
+        assert_eq!(err.exit_code(), exit::IO);
+
+        let io = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied");
+        let err = Err::<(), _>(io).context("Failed to write").unwrap_err();
+        assert!(matches!(err, TmpError::PermissionDenied { .. }));
+        assert_eq!(err.to_string(), "Failed to write: permission denied");
+        assert_eq!(err.exit_code(), exit::PERMISSION_DENIED);
+
+        let yaml = serde_yaml::from_str::<Vec<String>>("[").unwrap_err();
+        let err = Err::<(), _>(yaml)
+            .with_context(|| "Failed to parse YAML config".to_string())
+            .unwrap_err();
+        assert_eq!(err.exit_code(), exit::CONFIG);
+
+        let yaml = serde_yaml::from_str::<Vec<String>>("- a\n- [b\n").unwrap_err();
+        let err = TmpError::config_parse(std::path::Path::new("tmp.yml"), yaml);
+        assert!(matches!(
+            err,
+            TmpError::ConfigParse {
+                location: Some((2, 3)),
+                ..
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Failed to parse \"tmp.yml\" at line 2, column 3: .[1]: invalid type: sequence, expected a string"
+        );
+        assert_eq!(err.exit_code(), exit::CONFIG);
+    }
+}
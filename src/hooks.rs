@@ -0,0 +1,149 @@
+use crate::error::{Context, Result, TmpError};
+use crate::vars::Vars;
+use log::{debug, error, info};
+use std::borrow::Cow;
+use std::os::fd::AsFd;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Run a kind's `post_create` commands for the new file at `path`, stopping at the first failure
+///
+/// Each command is rendered with `vars`, every value shell-quoted, and run through `sh -c` from
+/// the file's directory, with every variable also exported as `TMP_<NAME>`. Hook output goes to
+/// stderr so stdout stays reserved for the path.
+pub fn run(commands: &[String], vars: &Vars, path: &Path) -> Result<()> {
+    let cwd = path.parent().unwrap_or(Path::new("/"));
+    for (index, command) in commands.iter().enumerate() {
+        let command = render(command, vars);
+        info!(
+            "Running post_create hook {n}/{total}: {command}",
+            n = index + 1,
+            total = commands.len()
+        );
+
+        let stdout = std::io::stderr()
+            .as_fd()
+            .try_clone_to_owned()
+            .context("Failed to redirect hook output")?;
+        let mut process = Command::new("sh");
+        process
+            .arg("-c")
+            .arg(&command)
+            .current_dir(cwd)
+            .stdout(Stdio::from(stdout));
+        for (key, value) in vars.iter() {
+            process.env(format!("TMP_{key}", key = key.to_uppercase()), value);
+        }
+
+        debug!("Hook cwd: {cwd:?}");
+        let status = process
+            .status()
+            .with_context(|| format!("Failed to run post_create hook: {command}"))?;
+
+        if !status.success() {
+            error!("post_create hook failed ({status}): {command}");
+            return Err(TmpError::HookFailed {
+                path: path.to_path_buf(),
+                index: index + 1,
+                total: commands.len(),
+                command,
+                status,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Commands as they would run, for dry runs
+pub fn preview(commands: &[String], vars: &Vars) -> Vec<String> {
+    commands.iter().map(|command| render(command, vars)).collect()
+}
+
+/// `command` with placeholders replaced by shell-quoted values, so a name can't inject commands
+fn render(command: &str, vars: &Vars) -> String {
+    vars.render_with(command, shell_quote)
+}
+
+/// `value` as a single shell word; values made only of safe characters are left as they are
+fn shell_quote(value: &str) -> Cow<'_, str> {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_-./:@%+=,".contains(c);
+    if !value.is_empty() && value.chars().all(safe) {
+        Cow::Borrowed(value)
+    } else {
+        Cow::Owned(format!("'{}'", value.replace('\'', r"'\''")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_run_renders_and_exports_vars() {
+        let tempdir = tempdir().unwrap();
+        let path = tempdir.path().join("hooked.sh");
+        let vars = Vars::for_file("sh", "sh", &path);
+
+        let commands = vec![
+            "echo {name} > first.txt".to_string(),
+            "echo $TMP_KIND > second.txt".to_string(),
+        ];
+        run(&commands, &vars, &path).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(tempdir.path().join("first.txt")).unwrap(),
+            "hooked.sh\n"
+        );
+        assert_eq!(fs::read_to_string(tempdir.path().join("second.txt")).unwrap(), "sh\n");
+    }
+
+    #[test]
+    fn test_run_stops_on_failure() {
+        let tempdir = tempdir().unwrap();
+        let vars = Vars::default();
+
+        let commands = vec!["exit 3".to_string(), "touch never.txt".to_string()];
+        let path = tempdir.path().join("x.sh");
+        let err = run(&commands, &vars, &path).unwrap_err();
+
+        assert!(matches!(err, TmpError::HookFailed { index: 1, total: 2, .. }));
+        assert!(err.to_string().contains("hook 1/2 `exit 3` failed"));
+        assert_eq!(err.created_path(), Some(path.as_path()));
+        assert!(!tempdir.path().join("never.txt").exists());
+    }
+
+    #[test]
+    fn test_run_quotes_values() {
+        let tempdir = tempdir().unwrap();
+        let path = tempdir.path().join("a b; echo INJECTED > pwned; it's.sh");
+        let vars = Vars::for_file("sh", "sh", &path);
+
+        run(&["echo {name} > out.txt".to_string()], &vars, &path).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(tempdir.path().join("out.txt")).unwrap(),
+            "a b; echo INJECTED > pwned; it's.sh\n"
+        );
+        assert!(!tempdir.path().join("pwned").exists());
+    }
+
+    #[test]
+    fn test_preview() {
+        let vars = Vars::for_file("py", "py", Path::new("/tmp/x.py"));
+        assert_eq!(
+            preview(&["git add {path}".to_string()], &vars),
+            vec!["git add /tmp/x.py"]
+        );
+
+        let vars = Vars::for_file("py", "py", Path::new("/tmp/a b.py"));
+        assert_eq!(
+            preview(&["git add {path}".to_string()], &vars),
+            vec!["git add '/tmp/a b.py'"]
+        );
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+}
@@ -0,0 +1,1558 @@
+//! Create files from kinds defined in a YAML config
+//!
+//! The `tmp` binary is a thin command line layer over this crate, so other tools can load the
+//! same config and render or create kinds themselves:
+//!
+//! ```no_run
+//! use tmp::{CreateOptions, Tmp, Vars, load_default};
+//!
+//! let tmp = Tmp::new(load_default(&std::env::current_dir()?)?)?;
+//! let options = CreateOptions::default().with_vars(Vars::from_iter([("author", "Ada")]));
+//! println!("{}", tmp.render_kind("py", Some("fetch"), &options)?);
+//! let path = tmp.create_file("py", "fetch", &options)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+#[doc(hidden)]
+pub mod app;
+mod cli;
+mod config;
+mod config_edit;
+mod editor;
+mod error;
+mod hooks;
+mod logging;
+mod man;
+mod manifest;
+mod naming;
+mod parents;
+mod picker;
+mod registry;
+mod requires;
+mod trash;
+mod trust;
+mod vars;
+
+pub use config::{Config, Kind, Origin, load_config, load_default};
+pub use error::{Result, TmpError};
+pub use manifest::ManifestEntry;
+pub use requires::Requires;
+pub use trash::Trash;
+pub use vars::Vars;
+
+use chrono::Local;
+use error::Context;
+use log::{debug, error, info, warn};
+use registry::{Entry, Registry};
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Per-invocation settings for `Tmp::create_file`
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct CreateOptions {
+    /// Overrides the kind's ttl
+    pub ttl: Option<Duration>,
+    /// Skip the kind's `post_create` hooks
+    pub no_hooks: bool,
+    /// Resolve the target path and check for conflicts, but write nothing and run nothing
+    pub dry_run: bool,
+    /// Create missing parent directories, in addition to kinds that always do
+    pub parents: bool,
+    /// Create bare names in the current directory, ignoring `dir` and `default_dir`
+    pub here: bool,
+    /// Extra variables for content and hooks, e.g. from `--var` and `--stdin`
+    pub vars: Vars,
+}
+
+impl CreateOptions {
+    pub fn with_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn with_no_hooks(mut self, no_hooks: bool) -> Self {
+        self.no_hooks = no_hooks;
+        self
+    }
+
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn with_parents(mut self, parents: bool) -> Self {
+        self.parents = parents;
+        self
+    }
+
+    pub fn with_here(mut self, here: bool) -> Self {
+        self.here = here;
+        self
+    }
+
+    pub fn with_vars(mut self, vars: Vars) -> Self {
+        self.vars = vars;
+        self
+    }
+}
+
+/// Outcome of `Tmp::create_batch`
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct BatchReport {
+    /// One result per attempted entry
+    pub results: Vec<(ManifestEntry, Result<PathBuf>)>,
+    /// Files deleted again after a failure in atomic mode
+    pub rolled_back: Vec<PathBuf>,
+}
+
+/// A kind's content as it will be written to disk
+#[derive(Debug)]
+struct Rendered {
+    content: String,
+    /// Line where the `{cursor}` marker was, if any
+    cursor_line: Option<usize>,
+}
+
+/// Per-invocation settings for `Tmp::delete_file`
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct DeleteOptions {
+    /// Delete even if the file was modified or not created by tmp
+    pub force: bool,
+    /// Move the file here instead of unlinking it
+    pub trash: Option<Trash>,
+    /// Remove now-empty parent directories that tmp created for the file
+    pub prune: bool,
+    /// Look for bare names in the current directory, ignoring `dir` and `default_dir`
+    pub here: bool,
+}
+
+impl DeleteOptions {
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    pub fn with_trash(mut self, trash: Option<Trash>) -> Self {
+        self.trash = trash;
+        self
+    }
+
+    pub fn with_prune(mut self, prune: bool) -> Self {
+        self.prune = prune;
+        self
+    }
+
+    pub fn with_here(mut self, here: bool) -> Self {
+        self.here = here;
+        self
+    }
+}
+
+/// Kinds loaded from a config, ready to render and create
+#[derive(Debug)]
+pub struct Tmp {
+    kinds: Vec<Kind>,
+    default_dir: Option<String>,
+    default_dir_root: Option<PathBuf>,
+    registry: Option<Registry>,
+}
+
+impl Tmp {
+    /// Fails with `TmpError::TemplateCycle` if templates refer to each other in a loop
+    pub fn new(config: Config) -> Result<Self> {
+        debug!("Creating Tmp instance with {len} kinds", len = config.kinds.len());
+
+        let mut kinds: Vec<Kind> = config
+            .kinds
+            .iter()
+            .cloned()
+            .map(|mut kind| {
+                debug!("Processing kind: {name}", name = kind.name);
+
+                // Interpolate templates
+                kind.content = config.expand_templates(&kind.content)?;
+                Ok(kind)
+            })
+            .collect::<Result<_>>()?;
+        // Explicit `order:` first; the sort is stable, so the rest stay in config order
+        kinds.sort_by_key(|kind| (kind.order.is_none(), kind.order));
+
+        Ok(Self {
+            kinds,
+            default_dir: config.default_dir,
+            default_dir_root: config.default_dir_root,
+            registry: None,
+        })
+    }
+
+    pub(crate) fn with_registry(mut self, registry: Registry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    pub fn find_kind(&self, name: &str) -> Option<&Kind> {
+        self.kinds.iter().find(|k| k.name == name)
+    }
+
+    /// Built-in variables for `kind` (and the file at `path`, if known) plus `extra`
+    fn vars(&self, kind: &Kind, path: Option<&Path>, extra: &Vars) -> Vars {
+        let mut vars = match path {
+            Some(path) => Vars::for_file(&kind.name, &kind.suffix, path),
+            None => Vars::for_kind(&kind.name, &kind.suffix),
+        };
+        vars.extend(extra);
+        vars
+    }
+
+    /// The kind's `post_create` commands as they would run for `path`
+    pub fn hook_preview(&self, kind_name: &str, path: &Path, extra: &Vars) -> Result<Vec<String>> {
+        let kind = self
+            .find_kind(kind_name)
+            .ok_or_else(|| TmpError::UnknownKind(kind_name.to_string()))?;
+        Ok(hooks::preview(&kind.post_create, &self.vars(kind, Some(path), extra)))
+    }
+
+    /// Fails with `TmpError::UnresolvedPlaceholder` for a `{tmp.KEY}` in `kind`'s content without a value
+    ///
+    /// Without a file (`has_file` false) its variables, such as `{tmp.stem}`, are left for later.
+    fn check_content(kind: &Kind, vars: &Vars, has_file: bool) -> Result<()> {
+        let undefined = vars.undefined(&kind.content);
+        match undefined
+            .into_iter()
+            .find(|key| has_file || !vars::FILE_KEYS.contains(key))
+        {
+            Some(key) => Err(TmpError::UnresolvedPlaceholder {
+                placeholder: format!("tmp.{key}"),
+                context: format!(
+                    "content of kind '{name}'; give it a value with --var {key}=VALUE",
+                    name = kind.name
+                ),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn render(&self, kind: &Kind, vars: &Vars) -> Rendered {
+        // Split at the marker before substituting, so values such as piped input can't contain or move it
+        let (before, after) = editor::split_cursor(&kind.content);
+        let before = vars.render_content(&before);
+        match after {
+            Some(after) => {
+                let cursor_line = before.matches('\n').count() + 1;
+                Rendered {
+                    content: before + &vars.render_content(&after),
+                    cursor_line: Some(cursor_line),
+                }
+            }
+            None => Rendered {
+                content: before,
+                cursor_line: None,
+            },
+        }
+    }
+
+    /// Content a file of `kind` would get, without writing it
+    ///
+    /// With a `filename`, file variables such as `{tmp.stem}` describe where it would be created.
+    pub fn render_kind(&self, kind_name: &str, filename: Option<&str>, options: &CreateOptions) -> Result<String> {
+        let kind = self
+            .find_kind(kind_name)
+            .ok_or_else(|| TmpError::UnknownKind(kind_name.to_string()))?;
+
+        let path = filename
+            .map(|filename| registry::absolute(Path::new(&self.full_filename(kind, filename, options.here)?)))
+            .transpose()?;
+        let vars = self.vars(kind, path.as_deref(), &options.vars);
+        Self::check_content(kind, &vars, path.is_some())?;
+
+        Ok(self.render(kind, &vars).content)
+    }
+
+    fn full_filename(&self, kind: &Kind, filename: &str, here: bool) -> Result<String> {
+        Ok(match self.kind_dir(kind, filename, here)? {
+            Some(dir) => dir.join(kind.filename(filename)).to_string_lossy().to_string(),
+            None => kind.filename(filename),
+        })
+    }
+
+    /// Write a new file of the given kind, returning its absolute path
+    pub fn create_file(&self, kind_name: &str, filename: &str, options: &CreateOptions) -> Result<PathBuf> {
+        info!("Creating file: {filename} with kind: {kind_name}");
+
+        let kind = self
+            .find_kind(kind_name)
+            .ok_or_else(|| TmpError::UnknownKind(kind_name.to_string()))?;
+
+        let in_kind_dir = self.kind_dir(kind, filename, options.here)?.is_some();
+        let full_filename = self.full_filename(kind, filename, options.here)?;
+
+        debug!("Full filename: {full_filename}");
+
+        if Path::new(&full_filename).exists() {
+            warn!("File {full_filename} already exists, refusing to overwrite");
+            return Err(TmpError::TargetExists(PathBuf::from(&full_filename)));
+        }
+
+        let path = registry::absolute(Path::new(&full_filename))?;
+
+        // Hooks and command requirements are the parts of a project config that run or probe commands
+        if let Some(config) = &kind.untrusted {
+            let runs_hooks = !kind.post_create.is_empty() && !options.no_hooks && !options.dry_run;
+            if runs_hooks || !kind.requires.command.is_empty() {
+                error!("Kind {kind_name} needs a trusted project config: {config:?}");
+                return Err(TmpError::Untrusted {
+                    kind: kind.name.clone(),
+                    config: config.clone(),
+                });
+            }
+        }
+
+        let reasons = kind.requires.unmet(path.parent().unwrap_or(Path::new("/")));
+        if !reasons.is_empty() {
+            error!("Requirements for kind {kind_name} not met: {reasons:?}");
+            return Err(TmpError::RequirementsNotMet {
+                kind: kind.name.clone(),
+                reasons,
+            });
+        }
+
+        // Checked before anything is written, so a ttl that's out of range leaves no file behind
+        let created = Local::now();
+        let expires = options
+            .ttl
+            .or(kind.ttl)
+            .map(|ttl| {
+                chrono::Duration::from_std(ttl)
+                    .ok()
+                    .and_then(|ttl| created.checked_add_signed(ttl))
+                    .ok_or_else(|| TmpError::InvalidDuration {
+                        value: format!("{secs}s", secs = ttl.as_secs()),
+                        reason: "out of range".to_string(),
+                    })
+            })
+            .transpose()?;
+
+        let vars = self.vars(kind, Some(&path), &options.vars);
+        Self::check_content(kind, &vars, true)?;
+
+        if options.dry_run {
+            info!(kind = kind.name.as_str(), path:% = path.display(), outcome = "dry_run"; "Dry run: would create {full_filename}");
+            return Ok(path);
+        }
+
+        let parent = path.parent().unwrap_or(Path::new("/"));
+        let dirs = if options.parents || kind.parents || in_kind_dir {
+            parents::create_missing(parent, kind.dir_mode)?
+        } else if !parent.exists() {
+            return Err(TmpError::MissingParent(parent.to_path_buf()));
+        } else {
+            Vec::new()
+        };
+
+        // create_new, so a file that appeared since the check above is never overwritten
+        let mut file = match OpenOptions::new().write(true).create_new(true).open(&full_filename) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                warn!("File {full_filename} was created by someone else, refusing to overwrite");
+                return Err(TmpError::TargetExists(PathBuf::from(&full_filename)));
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to create file: {full_filename}")),
+        };
+
+        let rendered = self.render(kind, &vars);
+        file.write_all(rendered.content.as_bytes())
+            .with_context(|| format!("Failed to write content to file: {full_filename}"))?;
+
+        if let Some(chmod) = kind.chmod {
+            debug!("Setting permissions to {chmod:o} for file: {full_filename}");
+            let permissions = std::fs::Permissions::from_mode(chmod);
+            fs::set_permissions(&full_filename, permissions)
+                .with_context(|| format!("Failed to set permissions for file: {full_filename}"))?;
+        }
+
+        if let Some(registry) = &self.registry {
+            let entry = Entry {
+                path: path.clone(),
+                kind: kind.name.clone(),
+                created,
+                expires,
+                hash: registry::hash_content(rendered.content.as_bytes()),
+                cwd: std::env::current_dir().unwrap_or_default(),
+                dirs,
+                trashed: None,
+            };
+            // The file is already written; without a record it only drops out of history, rm and gc
+            if let Err(e) = registry.record(entry) {
+                warn!(path:% = path.display(); "Failed to record {path:?} in the registry: {e}");
+            }
+        }
+
+        info!(kind = kind.name.as_str(), path:% = path.display(), outcome = "created"; "Successfully created file: {full_filename}");
+
+        // A failing hook leaves the file in place; the error says where
+        if !options.no_hooks && !kind.post_create.is_empty() {
+            hooks::run(&kind.post_create, &vars, &path)?;
+        }
+
+        Ok(path)
+    }
+
+    /// Open a file created from `kind_name` in `$VISUAL`/`$EDITOR`, at the kind's `{cursor}` marker
+    ///
+    /// Kept apart from `create_file` so a failing editor never undoes a successful create.
+    pub fn open_in_editor(&self, kind_name: &str, path: &Path, options: &CreateOptions) -> Result<()> {
+        let kind = self
+            .find_kind(kind_name)
+            .ok_or_else(|| TmpError::UnknownKind(kind_name.to_string()))?;
+        let rendered = self.render(kind, &self.vars(kind, Some(path), &options.vars));
+        editor::open(path, rendered.cursor_line)
+    }
+
+    /// Create every entry in order, reporting each result
+    ///
+    /// In `atomic` mode the first failure stops the batch and deletes the files already created,
+    /// including one whose hook failed. Hooks that already ran are not undone.
+    pub fn create_batch(&self, entries: &[ManifestEntry], options: &CreateOptions, atomic: bool) -> BatchReport {
+        let mut report = BatchReport::default();
+        // Paths earlier entries took, which a dry run never writes for later entries to see
+        let mut reserved = HashSet::new();
+
+        for entry in entries {
+            let result = self.create_entry(entry, options, &reserved);
+            if let Ok(path) = &result {
+                reserved.insert(path.clone());
+            }
+            let failed = result.is_err();
+            report.results.push((entry.clone(), result));
+
+            if failed && atomic {
+                warn!("Batch entry failed in atomic mode, rolling back");
+                report.rolled_back = self.roll_back(&report.results, options);
+                break;
+            }
+        }
+
+        report
+    }
+
+    fn create_entry(
+        &self,
+        entry: &ManifestEntry,
+        options: &CreateOptions,
+        reserved: &HashSet<PathBuf>,
+    ) -> Result<PathBuf> {
+        let mut options = options.clone();
+        for (key, value) in &entry.vars {
+            options.vars.set(key, value);
+        }
+
+        let filename = match &entry.name {
+            Some(name) => name.clone(),
+            None => self.free_name(&entry.kind, options.here, reserved)?,
+        };
+        let path = self.create_file(&entry.kind, &filename, &options)?;
+        if reserved.contains(&path) {
+            return Err(TmpError::TargetExists(path));
+        }
+        Ok(path)
+    }
+
+    fn roll_back(&self, results: &[(ManifestEntry, Result<PathBuf>)], options: &CreateOptions) -> Vec<PathBuf> {
+        if options.dry_run {
+            return Vec::new();
+        }
+
+        let delete_options = DeleteOptions {
+            prune: true,
+            ..Default::default()
+        };
+        results
+            .iter()
+            .filter_map(|(_, result)| match result {
+                Ok(path) => Some(path.as_path()),
+                Err(e) => e.created_path(),
+            })
+            .filter(
+                |path| match remove_created_file(path, true, &delete_options, self.registry.as_ref()) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        error!("Failed to roll back {path:?}: {e}");
+                        false
+                    }
+                },
+            )
+            .map(Path::to_path_buf)
+            .collect()
+    }
+
+    pub fn delete_file(&self, kind_name: &str, filename: &str, options: &DeleteOptions) -> Result<()> {
+        info!("Deleting file: {filename} with kind: {kind_name}");
+
+        let kind = self
+            .find_kind(kind_name)
+            .ok_or_else(|| TmpError::UnknownKind(kind_name.to_string()))?;
+
+        let full_filename = self.full_filename(kind, filename, options.here)?;
+
+        debug!("Full filename to delete: {full_filename}");
+
+        if !Path::new(&full_filename).exists() {
+            warn!("File {full_filename} does not exist, nothing to delete");
+            return Ok(());
+        }
+
+        // The file is safe to delete if it is exactly what the kind renders or what tmp recorded writing
+        let path = Path::new(&full_filename);
+        let hash = registry::hash_file(path)?;
+        let recorded = match &self.registry {
+            Some(registry) => registry.find(path)?.is_some_and(|entry| entry.hash == hash),
+            None => false,
+        };
+        let rendered = self.render(
+            kind,
+            &self.vars(kind, Some(&registry::absolute(path)?), &Vars::default()),
+        );
+        let pristine = recorded || hash == registry::hash_content(rendered.content.as_bytes());
+
+        remove_created_file(path, pristine, options, self.registry.as_ref())?;
+
+        info!("Successfully deleted file: {full_filename}");
+        Ok(())
+    }
+
+    /// The configured directory a bare `name` of `kind` goes in; explicit paths and `here` use the cwd
+    fn kind_dir(&self, kind: &Kind, name: &str, here: bool) -> Result<Option<PathBuf>> {
+        if here || name.contains('/') {
+            return Ok(None);
+        }
+
+        let (dir, root) = match (&kind.dir, &self.default_dir) {
+            (Some(dir), _) => (dir, &kind.root),
+            (None, Some(dir)) => (dir, &self.default_dir_root),
+            (None, None) => return Ok(None),
+        };
+        let rendered = Vars::for_kind(&kind.name, &kind.suffix).render(dir);
+        if let Some(placeholder) = vars::unresolved(&rendered) {
+            return Err(TmpError::UnresolvedPlaceholder {
+                placeholder: placeholder.to_string(),
+                context: format!("dir of kind '{name}'", name = kind.name),
+            });
+        }
+        let dir = config::expand_tilde(&rendered)?;
+        Ok(Some(match root {
+            Some(root) => root.join(dir),
+            None => dir,
+        }))
+    }
+
+    /// A name for a kind's file when none was given, avoiding existing files
+    pub fn default_name(&self, kind_name: &str, here: bool) -> Result<String> {
+        self.free_name(kind_name, here, &HashSet::new())
+    }
+
+    /// Like `default_name`, also avoiding the absolute paths in `reserved`
+    fn free_name(&self, kind_name: &str, here: bool, reserved: &HashSet<PathBuf>) -> Result<String> {
+        let kind = self
+            .find_kind(kind_name)
+            .ok_or_else(|| TmpError::UnknownKind(kind_name.to_string()))?;
+        let pattern = kind.default_name.as_deref().unwrap_or(naming::DEFAULT_PATTERN);
+        let dir = self.kind_dir(kind, pattern, here)?.unwrap_or_default();
+
+        Ok(naming::first_free_name(pattern, &kind.name, |name| {
+            let path = dir.join(kind.filename(name));
+            path.exists() || std::path::absolute(&path).is_ok_and(|path| reserved.contains(&path))
+        }))
+    }
+
+    /// Kinds with an `order:` first, then the rest in the order the config declares them
+    pub fn kinds(&self) -> &[Kind] {
+        &self.kinds
+    }
+}
+
+/// Unlink or trash a file tmp recorded creating, refusing it if it changed since unless forced
+pub(crate) fn remove_recorded_file(
+    entry: &registry::Entry,
+    options: &DeleteOptions,
+    registry: &Registry,
+) -> Result<()> {
+    let pristine = registry::hash_file(&entry.path)? == entry.hash;
+    remove_created_file(&entry.path, pristine, options, Some(registry))
+}
+
+/// Unlink or trash a file, refusing files that tmp can't vouch for unless forced
+pub(crate) fn remove_created_file(
+    path: &Path,
+    pristine: bool,
+    options: &DeleteOptions,
+    registry: Option<&Registry>,
+) -> Result<()> {
+    if !pristine && !options.force {
+        error!(path:% = path.display(), outcome = "refused"; "Refusing to delete {path:?}: modified or not created by tmp");
+        return Err(TmpError::Refused(path.to_path_buf()));
+    }
+
+    let dirs = match registry {
+        Some(registry) => registry.find(path)?.map(|entry| entry.dirs).unwrap_or_default(),
+        None => Vec::new(),
+    };
+
+    match &options.trash {
+        Some(trash) => {
+            let location = trash.put(path)?;
+            if let Some(registry) = registry {
+                registry.mark_trashed(path, &location)?;
+            }
+            info!(path:% = path.display(), outcome = "trashed"; "Trashed {path:?}");
+        }
+        None => {
+            fs::remove_file(path).with_context(|| format!("Failed to delete file: {path:?}"))?;
+            if let Some(registry) = registry {
+                registry.forget(path)?;
+            }
+            info!(path:% = path.display(), outcome = "deleted"; "Deleted {path:?}");
+        }
+    }
+
+    if options.prune {
+        parents::prune_empty(&dirs)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_kinds_sorted_by_order() {
+        let kind = |name: &str, order: Option<i32>| Kind {
+            name: name.to_string(),
+            order,
+            ..Default::default()
+        };
+        let config = Config {
+            kinds: vec![kind("c", None), kind("b", Some(2)), kind("a", None), kind("d", Some(1))],
+            ..Default::default()
+        };
+
+        let tmp = Tmp::new(config).unwrap();
+        let names: Vec<&str> = tmp.kinds().iter().map(|k| k.name.as_str()).collect();
+        assert_eq!(names, vec!["d", "b", "c", "a"]);
+    }
+
+    #[test]
+    fn test_template_interpolation() {
+        let kinds = vec![Kind {
+            name: "test".to_string(),
+            chmod: Some(0o755),
+            suffix: "sh".to_string(),
+            content: "{header}\necho {message}".to_string(),
+            ..Default::default()
+        }];
+
+        let mut templates = HashMap::new();
+        templates.insert("header".to_string(), "#!/bin/bash".to_string());
+        templates.insert("message".to_string(), "Hello World".to_string());
+
+        let processed = Tmp::new(Config {
+            kinds,
+            templates,
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(processed.kinds.len(), 1);
+        let kind = &processed.kinds[0];
+        assert_eq!(kind.name, "test");
+        assert_eq!(kind.content, "#!/bin/bash\necho Hello World");
+    }
+
+    #[test]
+    fn test_find_kind() {
+        let kinds = vec![
+            Kind {
+                name: "first".to_string(),
+                chmod: Some(0o644),
+                suffix: "txt".to_string(),
+                content: "content1".to_string(),
+                ..Default::default()
+            },
+            Kind {
+                name: "second".to_string(),
+                chmod: Some(0o755),
+                suffix: "sh".to_string(),
+                content: "content2".to_string(),
+                ..Default::default()
+            },
+        ];
+
+        let config = Config {
+            kinds: kinds.clone(),
+            templates: HashMap::new(),
+            ..Default::default()
+        };
+
+        let tmp = Tmp::new(config).unwrap();
+
+        let found = tmp.find_kind("second").unwrap();
+        assert_eq!(found.name, "second");
+        assert_eq!(found.chmod, Some(0o755));
+
+        assert!(tmp.find_kind("nonexistent").is_none());
+    }
+
+    #[test]
+    fn test_create_file() {
+        let tempdir = tempdir().unwrap();
+        let file_path = tempdir.path().join("test-file.sh");
+
+        let kinds = vec![Kind {
+            name: "test".to_string(),
+            chmod: Some(0o755),
+            suffix: "sh".to_string(),
+            content: "#!/bin/bash\necho 'test'\n".to_string(),
+            ..Default::default()
+        }];
+
+        let config = Config {
+            kinds,
+            templates: HashMap::new(),
+            ..Default::default()
+        };
+
+        let tmp = Tmp::new(config).unwrap();
+
+        // Use absolute path for filename
+        let filename_without_suffix = file_path.with_extension("").to_string_lossy().to_string();
+        tmp.create_file("test", &filename_without_suffix, &CreateOptions::default())
+            .unwrap();
+
+        // Verify file exists
+        assert!(file_path.exists());
+
+        // Verify content
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert_eq!(content, "#!/bin/bash\necho 'test'\n");
+
+        // Verify permissions
+        let metadata = fs::metadata(&file_path).unwrap();
+        let permissions = metadata.permissions();
+        assert_eq!(permissions.mode() & 0o777, 0o755);
+    }
+
+    #[test]
+    fn test_delete_file() {
+        let tempdir = tempdir().unwrap();
+        let file_path = tempdir.path().join("to-delete.txt");
+
+        // Create a file first
+        fs::write(&file_path, "content").unwrap();
+        assert!(file_path.exists());
+
+        let kinds = vec![Kind {
+            name: "test".to_string(),
+            chmod: None,
+            suffix: "txt".to_string(),
+            content: "content".to_string(),
+            ..Default::default()
+        }];
+
+        let config = Config {
+            kinds,
+            templates: HashMap::new(),
+            ..Default::default()
+        };
+
+        let tmp = Tmp::new(config).unwrap();
+
+        // Use absolute path for filename without suffix
+        let filename_without_suffix = file_path.with_extension("").to_string_lossy().to_string();
+        tmp.delete_file("test", &filename_without_suffix, &DeleteOptions::default())
+            .unwrap();
+
+        assert!(!file_path.exists());
+    }
+
+    #[test]
+    fn test_create_file_unknown_kind() {
+        let config = Config {
+            kinds: vec![],
+            templates: HashMap::new(),
+            ..Default::default()
+        };
+
+        let tmp = Tmp::new(config).unwrap();
+
+        let result = tmp.create_file("unknown", "test.txt", &CreateOptions::default());
+        assert!(matches!(result, Err(TmpError::UnknownKind(kind)) if kind == "unknown"));
+    }
+
+    #[test]
+    fn test_chmod_default_value() {
+        let tempdir = tempdir().unwrap();
+        let file_path = tempdir.path().join("no-chmod.txt");
+
+        let kinds = vec![Kind {
+            name: "no-chmod".to_string(),
+            chmod: None,
+            suffix: "txt".to_string(),
+            content: "content".to_string(),
+            ..Default::default()
+        }];
+
+        let config = Config {
+            kinds,
+            templates: HashMap::new(),
+            ..Default::default()
+        };
+
+        let tmp = Tmp::new(config).unwrap();
+
+        // Use absolute path for filename without suffix
+        let filename_without_suffix = file_path.with_extension("").to_string_lossy().to_string();
+        tmp.create_file("no-chmod", &filename_without_suffix, &CreateOptions::default())
+            .unwrap();
+
+        let metadata = fs::metadata(&file_path).unwrap();
+        let permissions = metadata.permissions();
+        // Should default to whatever the system default is (0o664 = 436 decimal)
+        assert_eq!(permissions.mode() & 0o777, 0o664);
+    }
+
+    #[test]
+    fn test_integration_template_interpolation() {
+        // Test with real config file if it exists
+        let config_path = std::path::Path::new(&std::env::var("HOME").unwrap()).join(".config/tmp/tmp.yml");
+
+        if config_path.exists() {
+            let config = load_config(&config_path).unwrap();
+            let tmp = Tmp::new(config).unwrap();
+
+            // Find the 'py' kind which should have template interpolation
+            if let Some(py_kind) = tmp.find_kind("py") {
+                // Verify that templates have been interpolated (no more {template} placeholders)
+                assert!(!py_kind.content.contains("{py3-header}"));
+                assert!(!py_kind.content.contains("{py-common}"));
+                assert!(!py_kind.content.contains("{py-footer}"));
+
+                // Verify that actual content has been interpolated
+                assert!(py_kind.content.contains("#!/usr/bin/env python3"));
+                assert!(py_kind.content.contains("if __name__ == '__main__':"));
+                assert!(py_kind.content.contains("import os"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_integration_file_creation_with_templates() {
+        use tempfile::tempdir;
+
+        // Test with real config file if it exists
+        let config_path = std::path::Path::new(&std::env::var("HOME").unwrap()).join(".config/tmp/tmp.yml");
+
+        if config_path.exists() {
+            let config = load_config(&config_path).unwrap();
+            let tmp = Tmp::new(config).unwrap();
+
+            // Test creating a file in a temporary directory
+            let tempdir = tempdir().unwrap();
+            let test_file_path = tempdir.path().join("test-integration");
+
+            // Create a py file
+            if tmp.find_kind("py").is_some() {
+                let filename = test_file_path.to_string_lossy();
+                tmp.create_file("py", &filename, &CreateOptions::default()).unwrap();
+
+                let created_file = tempdir.path().join("test-integration.py");
+                assert!(created_file.exists());
+
+                // Read the content and verify template interpolation worked
+                let content = fs::read_to_string(&created_file).unwrap();
+                assert!(content.contains("#!/usr/bin/env python3"));
+                assert!(content.contains("if __name__ == '__main__':"));
+                assert!(content.contains("def main(args):"));
+                assert!(!content.contains("{py3-header}"));
+                assert!(!content.contains("{py-footer}"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_chmod_from_config() {
+        let tempdir = tempdir().unwrap();
+        let file_path = tempdir.path().join("chmod-test.sh");
+
+        let kinds = vec![Kind {
+            name: "test-exec".to_string(),
+            chmod: Some(509), // This should be decimal 509 = octal 775
+            suffix: "sh".to_string(),
+            content: "#!/bin/bash\necho test".to_string(),
+            ..Default::default()
+        }];
+
+        let config = Config {
+            kinds,
+            templates: HashMap::new(),
+            ..Default::default()
+        };
+
+        let tmp = Tmp::new(config).unwrap();
+
+        // Use absolute path for filename without suffix
+        let filename_without_suffix = file_path.with_extension("").to_string_lossy().to_string();
+        tmp.create_file("test-exec", &filename_without_suffix, &CreateOptions::default())
+            .unwrap();
+
+        let metadata = fs::metadata(&file_path).unwrap();
+        let permissions = metadata.permissions();
+        // Should be 0o775 (509 decimal) = rwxrwxr-x
+        assert_eq!(permissions.mode() & 0o777, 0o775);
+    }
+
+    #[test]
+    fn test_create_and_delete_file_update_registry() {
+        let tempdir = tempdir().unwrap();
+        let file_path = tempdir.path().join("registered.py");
+        let registry = Registry::new(tempdir.path().join("registry.yml"));
+
+        let kinds = vec![Kind {
+            name: "py".to_string(),
+            chmod: None,
+            suffix: "py".to_string(),
+            content: "print('hi')\n".to_string(),
+            ..Default::default()
+        }];
+
+        let config = Config {
+            kinds,
+            templates: HashMap::new(),
+            ..Default::default()
+        };
+
+        let tmp = Tmp::new(config).unwrap().with_registry(registry.clone());
+
+        let filename_without_suffix = file_path.with_extension("").to_string_lossy().to_string();
+        tmp.create_file("py", &filename_without_suffix, &CreateOptions::default())
+            .unwrap();
+
+        let entry = registry.find(&file_path).unwrap().unwrap();
+        assert_eq!(entry.kind, "py");
+        assert_eq!(entry.hash, registry::hash_file(&file_path).unwrap());
+
+        tmp.delete_file("py", &filename_without_suffix, &DeleteOptions::default())
+            .unwrap();
+        assert!(registry.find(&file_path).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_delete_file_refuses_modified_file() {
+        let tempdir = tempdir().unwrap();
+        let file_path = tempdir.path().join("work.txt");
+        fs::write(&file_path, "real work").unwrap();
+
+        let kinds = vec![Kind {
+            name: "test".to_string(),
+            suffix: "txt".to_string(),
+            content: "content".to_string(),
+            ..Default::default()
+        }];
+
+        let config = Config {
+            kinds,
+            templates: HashMap::new(),
+            ..Default::default()
+        };
+
+        let tmp = Tmp::new(config).unwrap();
+        let filename_without_suffix = file_path.with_extension("").to_string_lossy().to_string();
+
+        let result = tmp.delete_file("test", &filename_without_suffix, &DeleteOptions::default());
+        assert!(matches!(result, Err(TmpError::Refused(_))));
+        assert!(file_path.exists());
+
+        let options = DeleteOptions {
+            force: true,
+            trash: Some(Trash::new(tempdir.path().join("Trash"))),
+            ..Default::default()
+        };
+        tmp.delete_file("test", &filename_without_suffix, &options).unwrap();
+        assert!(!file_path.exists());
+        assert!(tempdir.path().join("Trash/files/work.txt").exists());
+    }
+
+    #[test]
+    fn test_default_name_skips_existing_files() {
+        let tempdir = tempdir().unwrap();
+        let pattern = tempdir.path().join("scratch-{n}").to_string_lossy().to_string();
+
+        let kinds = vec![Kind {
+            name: "txt".to_string(),
+            suffix: "txt".to_string(),
+            content: "content".to_string(),
+            default_name: Some(pattern),
+            ..Default::default()
+        }];
+
+        let config = Config {
+            kinds,
+            templates: HashMap::new(),
+            ..Default::default()
+        };
+
+        let tmp = Tmp::new(config).unwrap();
+
+        let first = tmp.default_name("txt", false).unwrap();
+        tmp.create_file("txt", &first, &CreateOptions::default()).unwrap();
+        let second = tmp.default_name("txt", false).unwrap();
+
+        assert!(first.ends_with("scratch-1"));
+        assert!(second.ends_with("scratch-2"));
+        assert!(tempdir.path().join("scratch-1.txt").exists());
+
+        // A dry run writes nothing, yet each unnamed entry still gets its own name
+        let unnamed = ManifestEntry {
+            kind: "txt".to_string(),
+            name: None,
+            vars: Default::default(),
+        };
+        let named = ManifestEntry {
+            name: Some(tempdir.path().join("scratch-3").to_string_lossy().to_string()),
+            ..unnamed.clone()
+        };
+        let dry_run = CreateOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+        let report = tmp.create_batch(&[unnamed.clone(), unnamed, named], &dry_run, false);
+        let paths: Vec<_> = report.results.iter().map(|(_, result)| result.as_ref().ok()).collect();
+        assert_eq!(paths[0], Some(&tempdir.path().join("scratch-2.txt")));
+        assert_eq!(paths[1], Some(&tempdir.path().join("scratch-3.txt")));
+        assert!(matches!(report.results[2].1, Err(TmpError::TargetExists(_))));
+        assert!(!tempdir.path().join("scratch-2.txt").exists());
+    }
+
+    #[test]
+    fn test_create_file_refuses_existing_file() {
+        let tempdir = tempdir().unwrap();
+        let file_path = tempdir.path().join("existing.txt");
+        fs::write(&file_path, "real work").unwrap();
+
+        let kinds = vec![Kind {
+            name: "test".to_string(),
+            suffix: "txt".to_string(),
+            content: "content".to_string(),
+            ..Default::default()
+        }];
+
+        let config = Config {
+            kinds,
+            templates: HashMap::new(),
+            ..Default::default()
+        };
+
+        let tmp = Tmp::new(config).unwrap();
+
+        let filename_without_suffix = file_path.with_extension("").to_string_lossy().to_string();
+        let err = tmp
+            .create_file("test", &filename_without_suffix, &CreateOptions::default())
+            .unwrap_err();
+        assert!(matches!(err, TmpError::TargetExists(_)));
+        assert_eq!(err.exit_code(), error::exit::CONFLICT);
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "real work");
+    }
+
+    #[test]
+    fn test_create_file_refuses_commands_from_untrusted_project() {
+        let tempdir = tempdir().unwrap();
+        let config = tempdir.path().join(".tmp.yml");
+        let mut kind = Kind::new("sh").with_suffix("sh");
+        kind.post_create = vec!["touch {path}.ran".to_string()];
+        kind.untrusted = Some(config.clone());
+        let tmp = Tmp::new(Config {
+            kinds: vec![kind],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let name = tempdir.path().join("x").to_string_lossy().to_string();
+        let err = tmp.create_file("sh", &name, &CreateOptions::default()).unwrap_err();
+        assert!(matches!(err, TmpError::Untrusted { ref config, .. } if config == &tempdir.path().join(".tmp.yml")));
+        assert!(!tempdir.path().join("x.sh").exists());
+
+        // Without hooks there's nothing to trust
+        let options = CreateOptions::default().with_no_hooks(true);
+        tmp.create_file("sh", &name, &options).unwrap();
+        assert!(!tempdir.path().join("x.sh.ran").exists());
+    }
+
+    #[test]
+    fn test_create_file_survives_an_unwritable_registry() {
+        let tempdir = tempdir().unwrap();
+        // A registry under a regular file can never be written
+        fs::write(tempdir.path().join("data"), "").unwrap();
+        let registry = Registry::new(tempdir.path().join("data/registry.yml"));
+
+        let config = Config {
+            kinds: vec![Kind::new("txt").with_suffix("txt")],
+            ..Default::default()
+        };
+        let tmp = Tmp::new(config).unwrap().with_registry(registry);
+
+        let name = tempdir.path().join("notes").to_string_lossy().to_string();
+        let path = tmp.create_file("txt", &name, &CreateOptions::default()).unwrap();
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn test_create_file_never_follows_a_dangling_symlink() {
+        let tempdir = tempdir().unwrap();
+        let link = tempdir.path().join("link.txt");
+        let elsewhere = tempdir.path().join("elsewhere.txt");
+        // `exists()` is false for a dangling symlink, so only create_new catches it
+        std::os::unix::fs::symlink(&elsewhere, &link).unwrap();
+
+        let config = Config {
+            kinds: vec![Kind {
+                name: "test".to_string(),
+                suffix: "txt".to_string(),
+                content: "content".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let tmp = Tmp::new(config).unwrap();
+
+        let err = tmp
+            .create_file("test", &link.to_string_lossy(), &CreateOptions::default())
+            .unwrap_err();
+        assert!(matches!(err, TmpError::TargetExists(_)));
+        assert!(!elsewhere.exists());
+    }
+
+    #[test]
+    fn test_cursor_marker_is_not_written() {
+        let tempdir = tempdir().unwrap();
+        let file_path = tempdir.path().join("cursor.sh");
+
+        let kinds = vec![Kind {
+            name: "sh".to_string(),
+            suffix: "sh".to_string(),
+            content: "#!/bin/bash\n{cursor}\n".to_string(),
+            ..Default::default()
+        }];
+
+        let config = Config {
+            kinds,
+            templates: HashMap::new(),
+            ..Default::default()
+        };
+
+        let tmp = Tmp::new(config).unwrap();
+        let rendered = tmp.render(tmp.find_kind("sh").unwrap(), &Vars::default());
+        assert_eq!(rendered.cursor_line, Some(2));
+
+        let filename_without_suffix = file_path.with_extension("").to_string_lossy().to_string();
+        tmp.create_file("sh", &filename_without_suffix, &CreateOptions::default())
+            .unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "#!/bin/bash\n\n");
+
+        // The pristine check compares against the content without the marker
+        tmp.delete_file("sh", &filename_without_suffix, &DeleteOptions::default())
+            .unwrap();
+        assert!(!file_path.exists());
+    }
+
+    #[test]
+    fn test_post_create_hooks() {
+        let tempdir = tempdir().unwrap();
+        let file_path = tempdir.path().join("hooked.sh");
+        let marker = tempdir.path().join("hooked.sh.done");
+
+        let kinds = vec![Kind {
+            name: "sh".to_string(),
+            suffix: "sh".to_string(),
+            content: "echo hi\n".to_string(),
+            post_create: vec!["touch {path}.done".to_string()],
+            ..Default::default()
+        }];
+
+        let config = Config {
+            kinds,
+            templates: HashMap::new(),
+            ..Default::default()
+        };
+
+        let tmp = Tmp::new(config).unwrap();
+        let filename_without_suffix = file_path.with_extension("").to_string_lossy().to_string();
+
+        let dry_run = CreateOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+        let path = tmp.create_file("sh", &filename_without_suffix, &dry_run).unwrap();
+        assert_eq!(path, file_path);
+        assert!(!file_path.exists());
+        assert_eq!(
+            tmp.hook_preview("sh", &path, &Vars::default()).unwrap(),
+            vec![format!("touch {}.done", file_path.display())]
+        );
+
+        let no_hooks = CreateOptions {
+            no_hooks: true,
+            ..Default::default()
+        };
+        tmp.create_file("sh", &filename_without_suffix, &no_hooks).unwrap();
+        assert!(file_path.exists());
+        assert!(!marker.exists());
+
+        fs::remove_file(&file_path).unwrap();
+        tmp.create_file("sh", &filename_without_suffix, &CreateOptions::default())
+            .unwrap();
+        assert!(marker.exists());
+    }
+
+    #[test]
+    fn test_create_file_checks_requirements() {
+        let tempdir = tempdir().unwrap();
+        let file_path = tempdir.path().join("lib.rs");
+
+        let kinds = vec![Kind {
+            name: "rs".to_string(),
+            suffix: "rs".to_string(),
+            content: "fn main() {}\n".to_string(),
+            requires: requires::Requires {
+                file_exists: vec!["Cargo.toml".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        }];
+
+        let config = Config {
+            kinds,
+            templates: HashMap::new(),
+            ..Default::default()
+        };
+
+        let tmp = Tmp::new(config).unwrap();
+        let filename_without_suffix = file_path.with_extension("").to_string_lossy().to_string();
+
+        let err = tmp
+            .create_file("rs", &filename_without_suffix, &CreateOptions::default())
+            .unwrap_err();
+        assert!(err.to_string().contains("Cargo.toml not found"));
+        assert!(matches!(err, TmpError::RequirementsNotMet { .. }));
+        assert_eq!(err.exit_code(), error::exit::REQUIREMENTS);
+        assert!(!file_path.exists());
+
+        fs::write(tempdir.path().join("Cargo.toml"), "").unwrap();
+        tmp.create_file("rs", &filename_without_suffix, &CreateOptions::default())
+            .unwrap();
+        assert!(file_path.exists());
+    }
+
+    #[test]
+    fn test_create_file_with_parents_and_prune() {
+        let tempdir = tempdir().unwrap();
+        let registry = Registry::new(tempdir.path().join("registry.yml"));
+        let file_path = tempdir.path().join("scripts/tools/foo.py");
+
+        let kinds = vec![Kind {
+            name: "py".to_string(),
+            suffix: "py".to_string(),
+            content: "print('hi')\n".to_string(),
+            ..Default::default()
+        }];
+
+        let config = Config {
+            kinds,
+            templates: HashMap::new(),
+            ..Default::default()
+        };
+
+        let tmp = Tmp::new(config).unwrap().with_registry(registry.clone());
+        let filename_without_suffix = file_path.with_extension("").to_string_lossy().to_string();
+
+        let err = tmp
+            .create_file("py", &filename_without_suffix, &CreateOptions::default())
+            .unwrap_err();
+        assert!(matches!(err, TmpError::MissingParent(ref parent) if parent == file_path.parent().unwrap()));
+        assert!(err.to_string().contains("use --parents"));
+        assert_eq!(err.exit_code(), error::exit::IO);
+
+        let options = CreateOptions {
+            parents: true,
+            ..Default::default()
+        };
+        tmp.create_file("py", &filename_without_suffix, &options).unwrap();
+        assert!(file_path.exists());
+        assert_eq!(
+            registry.find(&file_path).unwrap().unwrap().dirs,
+            vec![tempdir.path().join("scripts"), tempdir.path().join("scripts/tools")]
+        );
+
+        let prune = DeleteOptions {
+            prune: true,
+            ..Default::default()
+        };
+        tmp.delete_file("py", &filename_without_suffix, &prune).unwrap();
+        assert!(!tempdir.path().join("scripts").exists());
+    }
+
+    #[test]
+    fn test_kind_dir_is_relative_to_the_project() {
+        let tempdir = tempdir().unwrap();
+        let repo = tempdir.path().join("repo");
+        let nested = repo.join("src/pkg");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(
+            repo.join(config::PROJECT_CONFIG),
+            "templates: {}\ndefault_dir: scratch\nkinds:\n  test:\n    suffix: py\n    content: ''\n    dir: tests\n",
+        )
+        .unwrap();
+
+        // Run from deep inside the project, the directories are still the project's
+        let config = config::load_layered(None, &nested, None).unwrap();
+        let tmp = Tmp::new(config).unwrap();
+        let path = tmp.create_file("test", "foo", &CreateOptions::default()).unwrap();
+        assert_eq!(path, repo.join("tests/foo.py"));
+        assert!(!nested.join("tests").exists());
+
+        let dry_run = CreateOptions::default().with_dry_run(true);
+        let path = tmp.create_file("sh", "foo", &dry_run).unwrap();
+        assert_eq!(path, repo.join("scratch/foo.sh"));
+    }
+
+    #[test]
+    fn test_kind_dir() {
+        let tempdir = tempdir().unwrap();
+        let scratch = tempdir.path().join("scratch");
+        let tests = tempdir.path().join("tests");
+
+        let kinds = vec![
+            Kind {
+                name: "py".to_string(),
+                suffix: "py".to_string(),
+                content: "print('hi')\n".to_string(),
+                ..Default::default()
+            },
+            Kind {
+                name: "test".to_string(),
+                suffix: "py".to_string(),
+                content: "def test(): pass\n".to_string(),
+                dir: Some(format!("{}/{{kind}}s", tempdir.path().display())),
+                ..Default::default()
+            },
+            Kind {
+                name: "typo".to_string(),
+                suffix: "txt".to_string(),
+                dir: Some("~/{knid}".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        let config = Config {
+            kinds,
+            templates: HashMap::new(),
+            default_dir: Some(scratch.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let tmp = Tmp::new(config).unwrap();
+
+        let path = tmp.create_file("py", "foo", &CreateOptions::default()).unwrap();
+        assert_eq!(path, scratch.join("foo.py"));
+
+        let path = tmp.create_file("test", "test_foo", &CreateOptions::default()).unwrap();
+        assert_eq!(path, tests.join("test_foo.py"));
+
+        // Explicit paths and --here ignore the configured directories
+        let explicit = tempdir.path().join("explicit").to_string_lossy().to_string();
+        let path = tmp.create_file("py", &explicit, &CreateOptions::default()).unwrap();
+        assert_eq!(path, tempdir.path().join("explicit.py"));
+
+        let here = CreateOptions {
+            here: true,
+            dry_run: true,
+            ..Default::default()
+        };
+        let path = tmp.create_file("py", "foo", &here).unwrap();
+        assert_eq!(path, std::env::current_dir().unwrap().join("foo.py"));
+
+        assert_eq!(tmp.default_name("py", false).unwrap(), "tmp");
+        tmp.delete_file("py", "foo", &DeleteOptions::default()).unwrap();
+
+        let err = tmp.create_file("typo", "foo", &CreateOptions::default()).unwrap_err();
+        assert!(matches!(err, TmpError::UnresolvedPlaceholder { ref placeholder, .. } if placeholder == "knid"));
+        assert!(!scratch.join("foo.py").exists());
+    }
+
+    #[test]
+    fn test_render_kind_with_vars() {
+        let kinds = vec![Kind {
+            name: "py".to_string(),
+            suffix: "py".to_string(),
+            content: "# {tmp.stem} by {tmp.author}\nBODY = \"{tmp.stdin}\"\nfor f in ${dir}; do {cursor}\n".to_string(),
+            ..Default::default()
+        }];
+
+        let config = Config {
+            kinds,
+            templates: HashMap::new(),
+            ..Default::default()
+        };
+
+        let tmp = Tmp::new(config).unwrap();
+
+        let mut vars = Vars::default();
+        vars.set("author", "Ada");
+        vars.set("stdin", "piped {tmp.stem}\n{cursor}");
+        let options = CreateOptions {
+            vars,
+            ..Default::default()
+        };
+
+        // Piped input is inserted as it is, after the cursor marker is gone
+        let content = tmp.render_kind("py", Some("fetch"), &options).unwrap();
+        assert_eq!(
+            content,
+            "# fetch by Ada\nBODY = \"piped {tmp.stem}\n{cursor}\"\nfor f in ${dir}; do \n"
+        );
+
+        // Without a name there are no file variables to fill in
+        let content = tmp.render_kind("py", None, &options).unwrap();
+        assert!(content.starts_with("# {tmp.stem} by Ada"));
+
+        // A variable without a value is an error rather than a literal placeholder in the file
+        let tempdir = tempdir().unwrap();
+        let name = tempdir.path().join("fetch").to_string_lossy().to_string();
+        let mut vars = Vars::default();
+        vars.set("stdin", "");
+        let options = CreateOptions::default().with_vars(vars);
+        for result in [
+            tmp.render_kind("py", None, &options).map(|_| ()),
+            tmp.create_file("py", &name, &options).map(|_| ()),
+        ] {
+            assert!(matches!(
+                result,
+                Err(TmpError::UnresolvedPlaceholder { ref placeholder, .. }) if placeholder == "tmp.author"
+            ));
+        }
+        assert!(!tempdir.path().join("fetch.py").exists());
+    }
+
+    #[test]
+    fn test_create_batch_atomic_rollback() {
+        let tempdir = tempdir().unwrap();
+        let registry = Registry::new(tempdir.path().join("registry.yml"));
+        let existing = tempdir.path().join("c.txt");
+        fs::write(&existing, "real work").unwrap();
+
+        let kinds = vec![Kind {
+            name: "txt".to_string(),
+            suffix: "txt".to_string(),
+            content: "{tmp.greeting}\n".to_string(),
+            ..Default::default()
+        }];
+
+        let config = Config {
+            kinds,
+            templates: HashMap::new(),
+            ..Default::default()
+        };
+
+        let tmp = Tmp::new(config).unwrap().with_registry(registry);
+        let entry = |name: &str, greeting: &str| ManifestEntry {
+            kind: "txt".to_string(),
+            name: Some(tempdir.path().join(name).to_string_lossy().to_string()),
+            vars: [("greeting".to_string(), greeting.to_string())].into(),
+        };
+        let entries = vec![entry("a", "hi"), entry("sub/b", "hey"), entry("c", "hello")];
+        let options = CreateOptions {
+            parents: true,
+            ..Default::default()
+        };
+
+        // Without atomic, the conflict is reported and the rest are kept
+        let report = tmp.create_batch(&entries, &options, false);
+        assert_eq!(report.results.len(), 3);
+        assert!(report.results[2].1.is_err());
+        assert!(report.rolled_back.is_empty());
+        assert_eq!(fs::read_to_string(tempdir.path().join("a.txt")).unwrap(), "hi\n");
+        assert_eq!(fs::read_to_string(tempdir.path().join("sub/b.txt")).unwrap(), "hey\n");
+
+        fs::remove_file(tempdir.path().join("a.txt")).unwrap();
+        fs::remove_file(tempdir.path().join("sub/b.txt")).unwrap();
+        fs::remove_dir(tempdir.path().join("sub")).unwrap();
+
+        // With atomic, everything created before the failure is removed again
+        let report = tmp.create_batch(&entries, &options, true);
+        assert_eq!(report.rolled_back.len(), 2);
+        assert!(!tempdir.path().join("a.txt").exists());
+        assert!(!tempdir.path().join("sub").exists());
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "real work");
+    }
+
+    #[test]
+    fn test_create_batch_atomic_rollback_after_hook_failure() {
+        let tempdir = tempdir().unwrap();
+        let registry = Registry::new(tempdir.path().join("registry.yml"));
+
+        let kinds = vec![
+            Kind::new("txt").with_suffix("txt"),
+            Kind {
+                post_create: vec!["false".to_string()],
+                ..Kind::new("bad").with_suffix("txt")
+            },
+        ];
+        let config = Config {
+            kinds,
+            templates: HashMap::new(),
+            ..Default::default()
+        };
+        let tmp = Tmp::new(config).unwrap().with_registry(registry.clone());
+        let entry = |kind: &str, name: &str| ManifestEntry {
+            kind: kind.to_string(),
+            name: Some(tempdir.path().join(name).to_string_lossy().to_string()),
+            vars: Default::default(),
+        };
+        let entries = vec![entry("txt", "a"), entry("bad", "b")];
+
+        // The hook's file is still created, and the error says so
+        let report = tmp.create_batch(&entries, &CreateOptions::default(), false);
+        let error = report.results[1].1.as_ref().unwrap_err();
+        let hooked = tempdir.path().join("b.txt");
+        assert_eq!(error.created_path(), Some(hooked.as_path()));
+        assert!(
+            error
+                .to_string()
+                .starts_with(&format!("Created {hooked:?}, but post_create hook 1/1"))
+        );
+        assert!(hooked.exists());
+
+        fs::remove_file(tempdir.path().join("a.txt")).unwrap();
+        fs::remove_file(&hooked).unwrap();
+        for path in ["a.txt", "b.txt"] {
+            registry.forget(&tempdir.path().join(path)).unwrap();
+        }
+
+        // With atomic, the file whose hook failed is rolled back with the rest
+        let report = tmp.create_batch(&entries, &CreateOptions::default(), true);
+        assert_eq!(report.rolled_back.len(), 2);
+        assert!(!tempdir.path().join("a.txt").exists());
+        assert!(!hooked.exists());
+        assert!(registry.load().unwrap().is_empty());
+    }
+}
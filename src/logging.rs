@@ -0,0 +1,319 @@
+use chrono::Local;
+use clap::ValueEnum;
+use env_logger::{Logger, Target};
+use log::kv::{Key, Value, VisitSource};
+use log::{LevelFilter, Log, Metadata, Record, warn};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Env var holding the log file's filter, e.g. `debug` or `tmp=trace`; falls back to `RUST_LOG`, then `info`
+pub const FILTER_ENV: &str = "TMP_LOG";
+/// Env var holding the size at which the log file is rotated, e.g. `512K` or `10M`
+pub const MAX_SIZE_ENV: &str = "TMP_LOG_MAX_SIZE";
+/// Env var holding how many rotated log files to keep
+pub const KEEP_ENV: &str = "TMP_LOG_KEEP";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// `[timestamp LEVEL target] message`
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+/// When to rotate the log file and how many old ones to keep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rotation {
+    pub max_size: u64,
+    pub keep: usize,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self {
+            max_size: 1024 * 1024,
+            keep: 3,
+        }
+    }
+}
+
+impl Rotation {
+    /// Read `TMP_LOG_MAX_SIZE` and `TMP_LOG_KEEP`, using the defaults for unset variables
+    pub fn from_env() -> Result<Self, String> {
+        let mut rotation = Self::default();
+        if let Ok(size) = std::env::var(MAX_SIZE_ENV) {
+            rotation.max_size = parse_size(&size).ok_or_else(|| format!("{MAX_SIZE_ENV}: invalid size '{size}'"))?;
+        }
+        if let Ok(keep) = std::env::var(KEEP_ENV) {
+            rotation.keep = keep
+                .parse()
+                .map_err(|_| format!("{KEEP_ENV}: invalid count '{keep}'"))?;
+        }
+        Ok(rotation)
+    }
+}
+
+/// Where logs go for this run
+#[derive(Debug, Default)]
+pub struct LogOptions {
+    /// Log file; `None` when no default could be determined
+    pub file: Option<PathBuf>,
+    /// Number of `-v` flags
+    pub verbose: u8,
+    /// `-q`: never mirror logs to stderr
+    pub quiet: bool,
+    /// Format of the log file; stderr always gets text
+    pub format: LogFormat,
+}
+
+/// Sends each record to the log file and, with `-v`, to stderr
+struct Tee {
+    file: Option<Logger>,
+    stderr: Option<Logger>,
+}
+
+impl Log for Tee {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.loggers().any(|logger| logger.enabled(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        for logger in self.loggers() {
+            logger.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        for logger in self.loggers() {
+            logger.flush();
+        }
+    }
+}
+
+impl Tee {
+    fn loggers(&self) -> impl Iterator<Item = &Logger> {
+        self.file.iter().chain(self.stderr.iter())
+    }
+}
+
+/// Level mirrored to stderr: off by default, then info, debug and trace for each `-v`
+pub fn stderr_level(verbose: u8, quiet: bool) -> LevelFilter {
+    match (quiet, verbose) {
+        (true, _) | (false, 0) => LevelFilter::Off,
+        (false, 1) => LevelFilter::Info,
+        (false, 2) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    }
+}
+
+/// Install the logger
+///
+/// A log file that can't be opened (no HOME, read-only filesystem) disables file logging instead of
+/// failing, so tmp keeps working in locked-down containers.
+pub fn init(options: &LogOptions) {
+    let (rotation, rotation_error) = match Rotation::from_env() {
+        Ok(rotation) => (rotation, None),
+        Err(e) => (Rotation::default(), Some(e)),
+    };
+    let (file, file_error) = match options.file.as_deref().map(|path| open_log_file(path, rotation)) {
+        Some(Ok(file)) => (Some(file), None),
+        Some(Err(e)) => (None, Some(e)),
+        None => (None, None),
+    };
+
+    let file = file.map(|file| {
+        let mut builder = env_logger::Builder::new();
+        match std::env::var(FILTER_ENV).or_else(|_| std::env::var("RUST_LOG")) {
+            Ok(filters) => builder.parse_filters(&filters),
+            Err(_) => builder.filter_level(LevelFilter::Info),
+        };
+        if options.format == LogFormat::Json {
+            builder.format(|buf, record| writeln!(buf, "{}", json_line(record)));
+        }
+        builder.target(Target::Pipe(Box::new(file))).build()
+    });
+
+    let level = stderr_level(options.verbose, options.quiet);
+    let stderr = (level != LevelFilter::Off).then(|| {
+        env_logger::Builder::new()
+            .filter_level(level)
+            .target(Target::Stderr)
+            .build()
+    });
+
+    let max_level = file
+        .iter()
+        .chain(stderr.iter())
+        .map(Logger::filter)
+        .max()
+        .unwrap_or(LevelFilter::Off);
+
+    if log::set_boxed_logger(Box::new(Tee { file, stderr })).is_ok() {
+        log::set_max_level(max_level);
+    }
+
+    if let Some(e) = rotation_error {
+        warn!("Ignoring log rotation setting, {e}");
+    }
+    if let (Some(path), Some(e)) = (&options.file, file_error) {
+        warn!("File logging disabled, can't open {path:?}: {e}");
+    }
+}
+
+fn open_log_file(path: &Path, rotation: Rotation) -> io::Result<File> {
+    if let Some(dir) = path.parent()
+        && !dir.as_os_str().is_empty()
+    {
+        fs::create_dir_all(dir)?;
+    }
+    rotate(path, rotation)?;
+    fs::OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Shift `tmp.log` to `tmp.log.1`, `tmp.log.1` to `tmp.log.2` and so on once it reaches the size limit
+fn rotate(path: &Path, rotation: Rotation) -> io::Result<()> {
+    let size = match fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if size < rotation.max_size {
+        return Ok(());
+    }
+
+    let numbered = |n: usize| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(format!(".{n}"));
+        PathBuf::from(name)
+    };
+    if rotation.keep == 0 {
+        return fs::remove_file(path);
+    }
+    for n in (1..rotation.keep).rev() {
+        if numbered(n).exists() {
+            fs::rename(numbered(n), numbered(n + 1))?;
+        }
+    }
+    fs::rename(path, numbered(1))
+}
+
+/// Parse a byte size such as `1048576`, `512K`, `10M` or `1G`
+fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let (digits, unit) = match value.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+        Some((index, _)) => value.split_at(index),
+        None => (value, ""),
+    };
+    let multiplier = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1024,
+        "M" | "MB" => 1024 * 1024,
+        "G" | "GB" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    digits.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// A record as a JSON object, with its key-value pairs (`kind`, `path`, `outcome`) as fields
+fn json_line(record: &Record) -> serde_json::Value {
+    struct Fields(serde_json::Map<String, serde_json::Value>);
+
+    impl<'kvs> VisitSource<'kvs> for Fields {
+        fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), log::kv::Error> {
+            self.0.insert(key.to_string(), value.to_string().into());
+            Ok(())
+        }
+    }
+
+    let mut fields = Fields(serde_json::Map::new());
+    fields
+        .0
+        .insert("timestamp".to_string(), Local::now().to_rfc3339().into());
+    fields.0.insert("level".to_string(), record.level().as_str().into());
+    fields.0.insert("target".to_string(), record.target().into());
+    fields.0.insert("message".to_string(), record.args().to_string().into());
+    // Fields are informational; a failing visit just leaves them out
+    let _ = record.key_values().visit(&mut fields);
+
+    serde_json::Value::Object(fields.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_stderr_level() {
+        assert_eq!(stderr_level(0, false), LevelFilter::Off);
+        assert_eq!(stderr_level(1, false), LevelFilter::Info);
+        assert_eq!(stderr_level(2, false), LevelFilter::Debug);
+        assert_eq!(stderr_level(5, false), LevelFilter::Trace);
+        assert_eq!(stderr_level(2, true), LevelFilter::Off);
+    }
+
+    #[test]
+    fn test_open_log_file() {
+        let tempdir = tempdir().unwrap();
+        let path = tempdir.path().join("logs/tmp.log");
+        open_log_file(&path, Rotation::default()).unwrap();
+        assert!(path.exists());
+
+        let blocker = tempdir.path().join("file");
+        fs::write(&blocker, "").unwrap();
+        assert!(open_log_file(&blocker.join("tmp.log"), Rotation::default()).is_err());
+    }
+
+    #[test]
+    fn test_rotate() {
+        let tempdir = tempdir().unwrap();
+        let path = tempdir.path().join("tmp.log");
+        let rotation = Rotation { max_size: 4, keep: 2 };
+        let read = |name: &str| fs::read_to_string(tempdir.path().join(name)).ok();
+
+        fs::write(&path, "abc").unwrap();
+        rotate(&path, rotation).unwrap();
+        assert_eq!(read("tmp.log").as_deref(), Some("abc"));
+
+        for content in ["first", "second", "third"] {
+            fs::write(&path, content).unwrap();
+            rotate(&path, rotation).unwrap();
+        }
+        assert_eq!(read("tmp.log"), None);
+        assert_eq!(read("tmp.log.1").as_deref(), Some("third"));
+        assert_eq!(read("tmp.log.2").as_deref(), Some("second"));
+        assert_eq!(read("tmp.log.3"), None);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1048576"), Some(1048576));
+        assert_eq!(parse_size("512K"), Some(512 * 1024));
+        assert_eq!(parse_size("10mb"), Some(10 * 1024 * 1024));
+        assert_eq!(parse_size("M"), None);
+        assert_eq!(parse_size("10X"), None);
+        assert_eq!(parse_size("18446744073709551615G"), None);
+    }
+
+    #[test]
+    fn test_json_line() {
+        let path = "/tmp/x.py";
+        let kvs = [("kind", "py"), ("path", path), ("outcome", "created")];
+        let line = json_line(
+            &Record::builder()
+                .args(format_args!("Successfully created file: {path}"))
+                .level(log::Level::Info)
+                .target("tmp")
+                .key_values(&kvs)
+                .build(),
+        );
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["kind"], "py");
+        assert_eq!(line["path"], "/tmp/x.py");
+        assert_eq!(line["outcome"], "created");
+        assert_eq!(line["message"], "Successfully created file: /tmp/x.py");
+        assert!(line["timestamp"].as_str().is_some_and(|t| t.starts_with("20")));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::cli::Cli;
+use clap::CommandFactory;
+use clap_mangen::Man;
+use std::io::{self, Write};
+
+/// The config file schema, which clap knows nothing about
+const CONFIGURATION: &str = r##".SH CONFIGURATION
+The config file is YAML, read from \fI~/.config/tmp/tmp.yml\fR unless \fB\-\-config\fR says otherwise.
+Built\-in kinds for sh, bash, py, rs, md, json, yaml and makefile are available without one.
+The user config is layered over them, and a \fI.tmp.yml\fR in the current directory or a parent is layered on top: its kinds and templates
+replace ones with the same name. \fBtmp list\fR shows where each kind comes from. \fBtmp init\fR writes a commented starter, and
+\fBtmp init \-\-project\fR a \fI.tmp.yml\fR.
+.PP
+A \fI.tmp.yml\fR arrives with whatever repository it is in, so its kinds may not run \fBpost_create\fR
+hooks or check \fBrequires.command\fR until you review it and run \fBtmp trust\fR. Trust is pinned to
+the file's content: after any edit it has to be trusted again.
+.PP
+It has three top\-level keys:
+.TP
+\fBkinds\fR
+A map from kind name to its definition. The name is what you pass as KIND.
+.TP
+\fBtemplates\fR
+A map from template name to text. \fB{name}\fR in a kind's content is replaced by the template,
+and templates may use other templates. A template that ends up including itself is an error.
+.TP
+\fBdefault_dir\fR
+Directory that bare NAMEs are created in, for kinds without their own \fBdir\fR.
+.PP
+Each kind accepts these keys; only \fBsuffix\fR and \fBcontent\fR are required.
+.TP
+\fBsuffix\fR
+File extension appended to NAME unless it already ends with it. May be empty.
+.TP
+\fBcontent\fR
+Text of the new file. See PLACEHOLDERS below.
+.TP
+\fBchmod\fR
+File mode applied after writing. Octal permissions written as plain numbers, such as
+\fB755\fR or \fB644\fR, are read as octal, so \fBchmod: 755\fR means \fBrwxr\-xr\-x\fR.
+Without it the umask applies.
+.TP
+\fBdescription\fR
+One line shown next to the kind in shell completions.
+.TP
+\fBtags\fR
+Labels such as \fBpython\fR or \fBtest\fR. \fBtmp list \-\-tag\fR \fITAG\fR shows only kinds with the tag,
+and \fBtmp list \-\-group\fR lists kinds under each of their tags.
+.TP
+\fBorder\fR
+Position of the kind in \fBtmp list\fR, completions and the picker; lower comes first. Kinds without
+one follow in the order the config declares them: project kinds, then user kinds, then built\-in kinds
+neither replaces. \fBtmp list \-\-sort name\fR sorts alphabetically instead.
+.TP
+\fBdefault_name\fR
+Name pattern used when no NAME is given, with \fB{date}\fR, \fB{time}\fR, \fB{kind}\fR,
+\fB{n}\fR (first free number) and \fB{rand}\fR. Defaults to \fBtmp\fR.
+.TP
+\fBdir\fR
+Directory that bare NAMEs of this kind are created in; may use \fB{kind}\fR and \fB~\fR.
+Names containing a slash and \fB\-\-here\fR ignore it. In a project config, relative \fBdir\fR and
+\fBdefault_dir\fR start at the project root, wherever in the project tmp runs.
+.TP
+\fBparents\fR, \fBdir_mode\fR
+Always create missing parent directories, with \fBdir_mode\fR (read like \fBchmod\fR) as their mode.
+.TP
+\fBttl\fR
+How long files of this kind live before \fBtmp gc\fR may delete them, e.g. \fB30m\fR, \fB2h\fR, \fB7d\fR or \fB1h30m\fR.
+.TP
+\fBopen\fR
+Open new files in \fB$VISUAL\fR or \fB$EDITOR\fR, at the \fB{cursor}\fR marker if there is one.
+.TP
+\fBpost_create\fR
+Shell commands run in order from the file's directory after it is written. Placeholder values
+are shell\-quoted, so write \fBgit add {path}\fR without quotes of your own. Every placeholder
+is also exported as \fBTMP_\fR\fINAME\fR, e.g. \fB"$TMP_PATH"\fR.
+.TP
+\fBrequires\fR
+Conditions checked before writing: \fBin_git_repo\fR, \fBfile_exists\fR, \fBcommand\fR and \fBenv\fR.
+The last three take one value or a list.
+.SS PLACEHOLDERS
+Content and hooks may use \fB{tmp.kind}\fR, \fB{tmp.suffix}\fR, \fB{tmp.date}\fR, \fB{tmp.time}\fR,
+\fB{tmp.path}\fR, \fB{tmp.name}\fR, \fB{tmp.stem}\fR and \fB{tmp.dir}\fR, plus \fB{tmp.stdin}\fR with
+\fB\-\-stdin\fR and \fB{tmp.\fR\fIKEY\fR\fB}\fR for any \fB\-\-var\fR \fIKEY\fR=\fIVALUE\fR. Hooks, \fBdir\fR and
+\fBdefault_name\fR may leave out the \fBtmp.\fR; content may not, so a file's own \fB${dir}\fR or
+\fBf"{name}"\fR is left alone. Values are inserted once and never expanded again. Write
+\fB{{tmp.name}\fR, \fB{{cursor}\fR or \fB{{\fR\fItemplate\fR\fB}\fR to keep a placeholder as it is; \fBadd\-kind\fR
+escapes them this way when it copies a file. A \fB{tmp.\fR\fIKEY\fR\fB}\fR in content without a value is an
+error; other unknown placeholders are left as they are.
+.SS EXAMPLE
+.nf
+kinds:
+  py:
+    suffix: py
+    chmod: 755
+    description: Python script
+    tags: [python]
+    content: |
+      {py\-header}
+      def main():
+          {cursor}pass
+templates:
+  py\-header: "#!/usr/bin/env python3"
+.fi
+"##;
+
+/// Write the `tmp(1)` man page: the clap-generated sections plus CONFIGURATION
+pub fn write(out: &mut dyn Write) -> io::Result<()> {
+    let man = Man::new(Cli::command());
+
+    man.render_title(out)?;
+    man.render_name_section(out)?;
+    man.render_synopsis_section(out)?;
+    man.render_description_section(out)?;
+    man.render_options_section(out)?;
+    man.render_subcommands_section(out)?;
+    man.render_extra_section(out)?;
+    out.write_all(CONFIGURATION.as_bytes())?;
+    man.render_version_section(out)?;
+    man.render_authors_section(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write() {
+        let mut page = Vec::new();
+        write(&mut page).unwrap();
+        let page = String::from_utf8(page).unwrap();
+
+        assert!(page.contains(".TH tmp 1"));
+        assert!(page.contains(".SH OPTIONS"));
+        assert!(page.contains("\\-\\-config"));
+        let configuration = page.find(".SH CONFIGURATION").unwrap();
+        assert!(page.find(".SH EXTRA").unwrap() < configuration);
+        assert!(configuration < page.find(".SH VERSION").unwrap());
+    }
+}
@@ -0,0 +1,92 @@
+use crate::error::{Context, Result, TmpError};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// One file to create with `tmp apply`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestEntry {
+    pub kind: String,
+    /// Name or path of the file; the kind's default name pattern is used when omitted
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Template variables for this file only
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>,
+}
+
+/// A list of files to scaffold in one go
+///
+/// ```yaml
+/// files:
+///   - kind: py
+///     name: scripts/fetch
+///     vars:
+///       author: Ada
+///   - kind: md
+///     name: README
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub files: Vec<ManifestEntry>,
+}
+
+pub fn load_manifest(path: &Path) -> Result<Manifest> {
+    debug!("Loading manifest from: {path:?}");
+
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read manifest: {path:?}"))?;
+    let manifest: Manifest = serde_yaml::from_str(&content).map_err(|e| TmpError::config_parse(path, e))?;
+
+    info!(
+        "Loaded {len} manifest entries from {path:?}",
+        len = manifest.files.len()
+    );
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_load_manifest() {
+        let tempdir = tempdir().unwrap();
+        let path = tempdir.path().join("manifest.yml");
+        fs::write(
+            &path,
+            "files:\n  - kind: py\n    name: scripts/fetch\n    vars:\n      author: Ada\n  - kind: md\n",
+        )
+        .unwrap();
+
+        let manifest = load_manifest(&path).unwrap();
+
+        assert_eq!(manifest.files.len(), 2);
+        assert_eq!(manifest.files[0].kind, "py");
+        assert_eq!(manifest.files[0].name.as_deref(), Some("scripts/fetch"));
+        assert_eq!(manifest.files[0].vars.get("author").map(String::as_str), Some("Ada"));
+        assert_eq!(manifest.files[1].name, None);
+        assert!(manifest.files[1].vars.is_empty());
+    }
+
+    #[test]
+    fn test_load_manifest_rejects_unknown_fields() {
+        let tempdir = tempdir().unwrap();
+        let path = tempdir.path().join("manifest.yml");
+        fs::write(&path, "files:\n  - kind: py\n    nmae: typo\n").unwrap();
+
+        let err = load_manifest(&path).unwrap_err();
+        assert!(matches!(
+            err,
+            TmpError::ConfigParse {
+                location: Some((3, 5)),
+                ..
+            }
+        ));
+        assert!(err.to_string().contains("nmae"));
+    }
+}
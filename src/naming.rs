@@ -0,0 +1,110 @@
+use chrono::{DateTime, Local};
+use log::debug;
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Pattern used when a kind doesn't configure `default_name`
+pub const DEFAULT_PATTERN: &str = "tmp";
+
+/// Expand the placeholders of a default name pattern
+///
+/// Supported placeholders: `{date}` (2024-01-31), `{time}` (235959), `{kind}`,
+/// `{n}` (sequence number) and `{rand}` (six random hex characters), each also as `{tmp.date}` etc.
+pub fn render_pattern(pattern: &str, kind: &str, n: usize, now: DateTime<Local>) -> String {
+    let mut name = pattern
+        .replace("{tmp.", "{")
+        .replace("{date}", &now.format("%Y-%m-%d").to_string())
+        .replace("{time}", &now.format("%H%M%S").to_string())
+        .replace("{kind}", kind)
+        .replace("{n}", &n.to_string());
+
+    while name.contains("{rand}") {
+        name = name.replacen("{rand}", &random_slug(), 1);
+    }
+
+    name
+}
+
+/// Pick the first name rendered from `pattern` that isn't `taken`
+///
+/// Patterns with `{n}` count up from 1. Patterns without it are tried as-is first and then with
+/// `-{n}` appended, so the default `tmp` pattern yields `tmp`, `tmp-1`, `tmp-2`, ...
+pub fn first_free_name(pattern: &str, kind: &str, taken: impl Fn(&str) -> bool) -> String {
+    let now = Local::now();
+    let numbered = pattern.contains("{n}");
+
+    if !numbered {
+        let name = render_pattern(pattern, kind, 0, now);
+        if !taken(&name) {
+            return name;
+        }
+    }
+
+    let pattern = if numbered {
+        pattern.to_string()
+    } else {
+        format!("{pattern}-{{n}}")
+    };
+
+    (1..)
+        .map(|n| render_pattern(&pattern, kind, n, now))
+        .find(|name| {
+            let taken = taken(name);
+            if taken {
+                debug!("Default name {name} is taken, trying the next one");
+            }
+            !taken
+        })
+        .expect("an unbounded sequence always yields a free name")
+}
+
+fn random_slug() -> String {
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let call = CALLS.fetch_add(1, Ordering::Relaxed);
+    let seed = format!("{nanos}-{pid}-{call}", pid = std::process::id());
+    format!("{:x}", Sha256::digest(seed.as_bytes()))[..6].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_render_pattern() {
+        let now = Local.with_ymd_and_hms(2024, 1, 31, 23, 59, 58).unwrap();
+
+        assert_eq!(render_pattern("tmp", "py", 0, now), "tmp");
+        assert_eq!(render_pattern("tmp-{date}-{n}", "py", 3, now), "tmp-2024-01-31-3");
+        assert_eq!(render_pattern("{kind}-{time}", "py", 0, now), "py-235958");
+        assert_eq!(render_pattern("{tmp.kind}-{n}", "py", 2, now), "py-2");
+
+        let random = render_pattern("scratch-{rand}", "py", 0, now);
+        assert_eq!(random.len(), "scratch-".len() + 6);
+        assert!(!random.contains('{'));
+    }
+
+    #[test]
+    fn test_first_free_name() {
+        let tempdir = tempdir().unwrap();
+        let taken = |name: &str| tempdir.path().join(format!("{name}.py")).exists();
+
+        assert_eq!(first_free_name("tmp", "py", taken), "tmp");
+        fs::write(tempdir.path().join("tmp.py"), "").unwrap();
+        assert_eq!(first_free_name("tmp", "py", taken), "tmp-1");
+        fs::write(tempdir.path().join("tmp-1.py"), "").unwrap();
+        assert_eq!(first_free_name("tmp", "py", taken), "tmp-2");
+
+        assert_eq!(first_free_name("note{n}", "py", taken), "note1");
+        fs::write(tempdir.path().join("note1.py"), "").unwrap();
+        assert_eq!(first_free_name("note{n}", "py", taken), "note2");
+    }
+}
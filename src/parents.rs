@@ -0,0 +1,85 @@
+use crate::error::{Context, Result};
+use log::{debug, info};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Create `dir` and any missing ancestors, returning the directories created, outermost first
+///
+/// With a `mode`, each created directory gets exactly that mode; otherwise the umask applies.
+pub fn create_missing(dir: &Path, mode: Option<u32>) -> Result<Vec<PathBuf>> {
+    let mut missing: Vec<PathBuf> = dir
+        .ancestors()
+        .take_while(|ancestor| !ancestor.as_os_str().is_empty() && !ancestor.exists())
+        .map(Path::to_path_buf)
+        .collect();
+    missing.reverse();
+
+    for dir in &missing {
+        debug!("Creating directory: {dir:?}");
+        fs::create_dir(dir).with_context(|| format!("Failed to create directory: {dir:?}"))?;
+        if let Some(mode) = mode {
+            fs::set_permissions(dir, fs::Permissions::from_mode(mode))
+                .with_context(|| format!("Failed to set permissions for directory: {dir:?}"))?;
+        }
+    }
+
+    if !missing.is_empty() {
+        info!("Created {len} parent directories under {dir:?}", len = missing.len());
+    }
+    Ok(missing)
+}
+
+/// Remove the given directories, innermost first, stopping at the first one that isn't empty
+pub fn prune_empty(dirs: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut pruned = Vec::new();
+
+    for dir in dirs.iter().rev() {
+        if !dir.exists() {
+            continue;
+        }
+        let empty = fs::read_dir(dir)
+            .with_context(|| format!("Failed to read directory: {dir:?}"))?
+            .next()
+            .is_none();
+        if !empty {
+            debug!("Not pruning non-empty directory: {dir:?}");
+            break;
+        }
+
+        fs::remove_dir(dir).with_context(|| format!("Failed to remove directory: {dir:?}"))?;
+        info!("Pruned empty directory: {dir:?}");
+        pruned.push(dir.clone());
+    }
+
+    Ok(pruned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_create_missing_and_prune() {
+        let tempdir = tempdir().unwrap();
+        let scripts = tempdir.path().join("scripts");
+        let tools = scripts.join("tools");
+
+        let created = create_missing(&tools, Some(0o750)).unwrap();
+        assert_eq!(created, vec![scripts.clone(), tools.clone()]);
+        assert_eq!(fs::metadata(&tools).unwrap().permissions().mode() & 0o777, 0o750);
+
+        // Nothing left to create the second time
+        assert!(create_missing(&tools, None).unwrap().is_empty());
+
+        // A file someone else put in `scripts` stops pruning there
+        fs::write(scripts.join("keep.txt"), "").unwrap();
+        assert_eq!(prune_empty(&created).unwrap(), vec![tools.clone()]);
+        assert!(scripts.exists());
+
+        fs::remove_file(scripts.join("keep.txt")).unwrap();
+        assert_eq!(prune_empty(&created).unwrap(), vec![scripts.clone()]);
+        assert!(!scripts.exists());
+    }
+}
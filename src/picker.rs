@@ -0,0 +1,341 @@
+//! Choosing a kind when KIND is omitted: a built-in fuzzy finder, or an external command like fzf
+
+use crate::Kind;
+use crate::config::group_by_tag;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, ClearType};
+use crossterm::{cursor, execute, queue};
+use log::debug;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// A line in the picker's list
+#[derive(Debug, Clone, Copy)]
+enum Row<'a> {
+    Tag(&'a str),
+    Kind(&'a Kind),
+}
+
+/// Let the user choose a kind on the terminal, with a preview of what it renders to
+///
+/// Draws on stderr so stdout stays clean. Returns `None` if the user cancels.
+pub fn pick(kinds: &[&Kind], preview: &dyn Fn(&Kind) -> String) -> io::Result<Option<String>> {
+    let mut tty = io::stderr();
+    let guard = TerminalGuard::enter(&mut tty)?;
+    let result = run(&mut tty, kinds, preview);
+    drop(guard);
+    result
+}
+
+/// Raw mode and the alternate screen, restored when dropped
+///
+/// Dropping restores the terminal on every path out of the picker, including errors and panics.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter(tty: &mut impl Write) -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        // From here on, a failure still leaves raw mode through the guard
+        let guard = Self;
+        execute!(tty, terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        if let Err(e) = execute!(io::stderr(), cursor::Show, terminal::LeaveAlternateScreen) {
+            debug!("Failed to leave the alternate screen: {e}");
+        }
+        if let Err(e) = terminal::disable_raw_mode() {
+            debug!("Failed to disable raw mode: {e}");
+        }
+    }
+}
+
+/// Let `command` choose a kind, e.g. `fzf`
+///
+/// It reads one `NAME<TAB>DESCRIPTION` line per kind on stdin and prints the chosen line; its first
+/// word is the kind. A failing exit status or empty output means the user cancelled.
+pub fn pick_with(command: &str, kinds: &[&Kind]) -> io::Result<Option<String>> {
+    debug!("Running picker: {command}");
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    for kind in kinds {
+        let description = kind.description.as_deref().unwrap_or_default();
+        // A picker that exits early closes the pipe; its output still counts
+        if writeln!(stdin, "{name}\t{description}", name = kind.name).is_err() {
+            break;
+        }
+    }
+    drop(stdin);
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        debug!("Picker exited with {status}", status = output.status);
+        return Ok(None);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .next()
+        .map(str::to_string))
+}
+
+/// How well `query` matches `text`, or `None` if its characters don't all appear in order
+///
+/// Matching ignores case. Consecutive matches and matches at the start of a word score higher,
+/// and so do matches that begin earlier in `text`.
+fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+
+    for wanted in query.chars().flat_map(char::to_lowercase) {
+        let found = position + text[position..].iter().position(|&c| c == wanted)?;
+        score += 1;
+        match previous {
+            Some(previous) if found == previous + 1 => score += 5,
+            Some(previous) => score -= (found - previous - 1) as i64,
+            None => score -= found as i64,
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+/// The rows to show: kinds grouped under their tags while `query` is empty, else the best matches first
+fn rows<'a>(kinds: &[&'a Kind], query: &str) -> Vec<Row<'a>> {
+    if query.is_empty() {
+        if kinds.iter().all(|kind| kind.tags.is_empty()) {
+            return kinds.iter().map(|&kind| Row::Kind(kind)).collect();
+        }
+        return group_by_tag(kinds)
+            .into_iter()
+            .flat_map(|(tag, kinds)| {
+                std::iter::once(Row::Tag(tag.unwrap_or("(untagged)"))).chain(kinds.into_iter().map(Row::Kind))
+            })
+            .collect();
+    }
+
+    let mut scored: Vec<(i64, &Kind)> = kinds
+        .iter()
+        .filter_map(|&kind| {
+            let text = format!(
+                "{name} {description} {tags}",
+                name = kind.name,
+                description = kind.description.as_deref().unwrap_or_default(),
+                tags = kind.tags.join(" ")
+            );
+            fuzzy_score(query, &text).map(|score| (score, kind))
+        })
+        .collect();
+    // Stable, so equally good matches keep the config's order
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored.into_iter().map(|(_, kind)| Row::Kind(kind)).collect()
+}
+
+fn run(tty: &mut impl Write, kinds: &[&Kind], preview: &dyn Fn(&Kind) -> String) -> io::Result<Option<String>> {
+    let mut query = String::new();
+    let mut selected = 0;
+
+    loop {
+        let rows = rows(kinds, &query);
+        let choices: Vec<usize> = (0..rows.len())
+            .filter(|&index| matches!(rows[index], Row::Kind(_)))
+            .collect();
+        selected = selected.min(choices.len().saturating_sub(1));
+        draw(tty, kinds.len(), &query, &rows, choices.get(selected).copied(), preview)?;
+
+        let Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            ..
+        }) = event::read()?
+        else {
+            continue;
+        };
+        let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+        match code {
+            KeyCode::Esc => return Ok(None),
+            KeyCode::Char('c') if ctrl => return Ok(None),
+            KeyCode::Enter => {
+                return Ok(choices.get(selected).and_then(|&index| match rows[index] {
+                    Row::Kind(kind) => Some(kind.name.clone()),
+                    Row::Tag(_) => None,
+                }));
+            }
+            KeyCode::Up => selected = selected.saturating_sub(1),
+            KeyCode::Char('p' | 'k') if ctrl => selected = selected.saturating_sub(1),
+            KeyCode::Down => selected += 1,
+            KeyCode::Char('n' | 'j') if ctrl => selected += 1,
+            KeyCode::Char('u') if ctrl => query.clear(),
+            KeyCode::Backspace => {
+                query.pop();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                query.push(c);
+                selected = 0;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn draw(
+    tty: &mut impl Write,
+    total: usize,
+    query: &str,
+    rows: &[Row],
+    selected: Option<usize>,
+    preview: &dyn Fn(&Kind) -> String,
+) -> io::Result<()> {
+    let (width, height) = terminal::size()?;
+    let (width, height) = (width as usize, height as usize);
+    let list_width = (width * 2 / 5).clamp(16, 40).min(width);
+    let body = height.saturating_sub(2);
+    // Grouped rows list a kind once per tag, so count kinds rather than rows
+    let matched = if query.is_empty() { total } else { rows.len() };
+
+    queue!(tty, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+    queue!(tty, Print(clip(&format!("> {query}"), width)), cursor::MoveTo(0, 1))?;
+    queue!(tty, Print(clip(&format!("  {matched}/{total}"), width)))?;
+
+    // Scroll so the selected row stays in view
+    let top = selected.map_or(0, |selected| (selected + 1).saturating_sub(body));
+    for (line, row) in rows.iter().skip(top).take(body).enumerate() {
+        queue!(tty, cursor::MoveTo(0, (line + 2) as u16))?;
+        match row {
+            Row::Tag(tag) => {
+                queue!(
+                    tty,
+                    SetAttribute(Attribute::Bold),
+                    Print(clip(&format!("{tag}:"), list_width)),
+                    SetAttribute(Attribute::Reset)
+                )?;
+            }
+            Row::Kind(kind) if selected == Some(top + line) => {
+                queue!(
+                    tty,
+                    SetAttribute(Attribute::Reverse),
+                    Print(clip(&format!("> {name}", name = kind.name), list_width)),
+                    SetAttribute(Attribute::Reset)
+                )?;
+            }
+            Row::Kind(kind) => {
+                queue!(tty, Print(clip(&format!("  {name}", name = kind.name), list_width)))?;
+            }
+        }
+    }
+
+    if let Some(Row::Kind(kind)) = selected.map(|index| rows[index])
+        && width > list_width + 2
+    {
+        let preview_width = width - list_width - 2;
+        let mut lines: Vec<String> = kind.description.iter().cloned().collect();
+        lines.extend(preview(kind).replace('\t', "    ").lines().map(str::to_string));
+        for (line, text) in lines.iter().take(body).enumerate() {
+            queue!(
+                tty,
+                cursor::MoveTo(list_width as u16, (line + 2) as u16),
+                Print("│ "),
+                Print(clip(text, preview_width))
+            )?;
+        }
+    }
+    tty.flush()
+}
+
+fn clip(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(name: &str, description: &str, tags: &[&str]) -> Kind {
+        Kind::new(name)
+            .with_description(description)
+            .with_tags(tags.iter().copied())
+    }
+
+    fn names(rows: &[Row]) -> Vec<String> {
+        rows.iter()
+            .map(|row| match row {
+                Row::Tag(tag) => format!("{tag}:"),
+                Row::Kind(kind) => kind.name.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert_eq!(fuzzy_score("xyz", "python"), None);
+        assert_eq!(fuzzy_score("", "python"), Some(0));
+        assert!(fuzzy_score("PY", "python").is_some());
+
+        // Consecutive beats scattered, and word starts beat the middle of words
+        assert!(fuzzy_score("py", "python").unwrap() > fuzzy_score("py", "pandas yaml").unwrap());
+        assert!(fuzzy_score("test", "py test").unwrap() > fuzzy_score("test", "pytest").unwrap());
+    }
+
+    #[test]
+    fn test_rows() {
+        let kinds = [
+            kind("sh", "Shell script", &["shell"]),
+            kind("py", "Python script", &["python"]),
+            kind("pytest", "Python test", &["python", "test"]),
+            kind("txt", "Notes", &[]),
+        ];
+        let kinds: Vec<&Kind> = kinds.iter().collect();
+
+        assert_eq!(
+            names(&rows(&kinds, "")),
+            vec![
+                "python:",
+                "py",
+                "pytest",
+                "shell:",
+                "sh",
+                "test:",
+                "pytest",
+                "(untagged):",
+                "txt"
+            ]
+        );
+        assert_eq!(names(&rows(&kinds, "py")), vec!["py", "pytest"]);
+        assert_eq!(names(&rows(&kinds, "pyt")), vec!["pytest", "py"]);
+        // Descriptions count too, but a scattered match ranks below an exact word
+        assert_eq!(names(&rows(&kinds, "notes")), vec!["txt", "pytest"]);
+        assert_eq!(names(&rows(&kinds, "zz")), Vec::<String>::new());
+
+        let untagged = [kind("b", "", &[]), kind("a", "", &[])];
+        let untagged: Vec<&Kind> = untagged.iter().collect();
+        assert_eq!(names(&rows(&untagged, "")), vec!["b", "a"]);
+    }
+
+    #[test]
+    fn test_pick_with() {
+        let kinds = [kind("sh", "Shell script", &[]), kind("py", "Python script", &[])];
+        let kinds: Vec<&Kind> = kinds.iter().collect();
+
+        assert_eq!(pick_with("tail -n 1", &kinds).unwrap(), Some("py".to_string()));
+        assert_eq!(pick_with("grep Shell", &kinds).unwrap(), Some("sh".to_string()));
+        // grep exits 1 when nothing matches, as fzf does when cancelled
+        assert_eq!(pick_with("grep nothing", &kinds).unwrap(), None);
+        assert_eq!(pick_with("true", &kinds).unwrap(), None);
+    }
+}
@@ -0,0 +1,418 @@
+use crate::error::{Context, Result};
+use chrono::{DateTime, Local};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+/// A single file that tmp created
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub kind: String,
+    pub created: DateTime<Local>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<DateTime<Local>>,
+    pub hash: String,
+    pub cwd: PathBuf,
+    /// Parent directories created for this file, outermost first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dirs: Vec<PathBuf>,
+    /// Location in the trash, if the file was trashed rather than deleted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trashed: Option<PathBuf>,
+}
+
+/// Outcome of `Registry::collect_garbage`
+#[derive(Debug, Default)]
+pub struct GcReport {
+    /// Expired, unmodified files that were (or would be) deleted
+    pub deleted: Vec<PathBuf>,
+    /// Expired files edited since creation; left in place and still registered
+    pub modified: Vec<PathBuf>,
+    /// Expired files that no longer exist; dropped from the registry
+    pub missing: Vec<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct RegistryFile {
+    entries: Vec<Entry>,
+}
+
+/// On-disk record of every file created by tmp, oldest first
+///
+/// Changes hold an advisory lock on `<registry>.lock` and replace the file by renaming a complete
+/// copy over it, so concurrent runs don't lose each other's entries and a crash never truncates it.
+#[derive(Debug, Clone)]
+pub struct Registry {
+    path: PathBuf,
+}
+
+impl Registry {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn load(&self) -> Result<Vec<Entry>> {
+        if !self.path.exists() {
+            debug!("Registry file not found, starting empty: {path:?}", path = self.path);
+            return Ok(Vec::new());
+        }
+
+        let content =
+            fs::read_to_string(&self.path).with_context(|| format!("Failed to read registry: {:?}", self.path))?;
+        let file: RegistryFile =
+            serde_yaml::from_str(&content).with_context(|| format!("Failed to parse registry: {:?}", self.path))?;
+
+        Ok(file.entries)
+    }
+
+    fn save(&self, entries: &[Entry]) -> Result<()> {
+        let file = RegistryFile {
+            entries: entries.to_vec(),
+        };
+        let content = serde_yaml::to_string(&file).context("Failed to serialize registry")?;
+        let temp = self.sibling(&format!("{pid}.tmp", pid = std::process::id()));
+        fs::write(&temp, content).with_context(|| format!("Failed to write registry: {temp:?}"))?;
+        fs::rename(&temp, &self.path).with_context(|| format!("Failed to replace registry: {:?}", self.path))?;
+
+        debug!(
+            "Saved {len} registry entries to {path:?}",
+            len = entries.len(),
+            path = self.path
+        );
+        Ok(())
+    }
+
+    /// `<registry>.<extension>`, next to the registry
+    fn sibling(&self, extension: &str) -> PathBuf {
+        let mut name = OsString::from(self.path.as_os_str());
+        name.push(".");
+        name.push(extension);
+        PathBuf::from(name)
+    }
+
+    /// Apply `change` to the entries under the registry's lock, saving them if it changed any
+    fn update<T>(&self, change: impl FnOnce(&mut Vec<Entry>) -> Result<T>) -> Result<T> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to create registry directory: {parent:?}"))?;
+        }
+        let lock_path = self.sibling("lock");
+        let lock = File::create(&lock_path).with_context(|| format!("Failed to open registry lock: {lock_path:?}"))?;
+        lock.lock()
+            .with_context(|| format!("Failed to lock registry: {lock_path:?}"))?;
+
+        let mut entries = self.load()?;
+        let before = entries.clone();
+        let result = change(&mut entries)?;
+        if entries != before {
+            self.save(&entries)?;
+        }
+        // Dropping the file releases the lock
+        drop(lock);
+        Ok(result)
+    }
+
+    /// Record a newly created file, replacing any stale entry for the same path
+    pub fn record(&self, entry: Entry) -> Result<()> {
+        info!(
+            "Recording {path:?} (kind: {kind}) in registry",
+            path = entry.path,
+            kind = entry.kind
+        );
+        self.update(|entries| {
+            entries.retain(|e| e.path != entry.path || e.trashed.is_some());
+            entries.push(entry);
+            Ok(())
+        })
+    }
+
+    /// The live (not trashed) entry for `path`
+    pub fn find(&self, path: &Path) -> Result<Option<Entry>> {
+        let path = absolute(path)?;
+        Ok(self
+            .load()?
+            .into_iter()
+            .rev()
+            .find(|e| e.path == path && e.trashed.is_none()))
+    }
+
+    /// The most recently created live (not trashed) entry
+    pub fn last(&self) -> Result<Option<Entry>> {
+        self.last_matching(|_| true)
+    }
+
+    /// The most recently created live (not trashed) entry that `matches` accepts
+    pub fn last_matching(&self, matches: impl Fn(&Entry) -> bool) -> Result<Option<Entry>> {
+        Ok(self
+            .load()?
+            .into_iter()
+            .rev()
+            .find(|e| e.trashed.is_none() && matches(e)))
+    }
+
+    /// Drop the live entry for `path`, returning it if it was registered
+    pub fn forget(&self, path: &Path) -> Result<Option<Entry>> {
+        let path = absolute(path)?;
+        self.update(|entries| {
+            let index = entries.iter().rposition(|e| e.path == path && e.trashed.is_none());
+            Ok(index.map(|index| entries.remove(index)))
+        })
+    }
+
+    /// Note that the live entry for `path` now lives at `location` in the trash
+    pub fn mark_trashed(&self, path: &Path, location: &Path) -> Result<()> {
+        let path = absolute(path)?;
+        self.update(|entries| {
+            if let Some(entry) = entries.iter_mut().rev().find(|e| e.path == path && e.trashed.is_none()) {
+                entry.trashed = Some(location.to_path_buf());
+            }
+            Ok(())
+        })
+    }
+
+    /// The most recently trashed entry, optionally restricted to an original `path`
+    pub fn find_trashed(&self, path: Option<&Path>) -> Result<Option<Entry>> {
+        let path = path.map(absolute).transpose()?;
+        Ok(self
+            .load()?
+            .into_iter()
+            .rev()
+            .find(|e| e.trashed.is_some() && path.as_ref().is_none_or(|p| &e.path == p)))
+    }
+
+    /// Note that the entry trashed at `location` is back at its original path
+    pub fn mark_restored(&self, location: &Path) -> Result<()> {
+        self.update(|entries| {
+            if let Some(entry) = entries.iter_mut().find(|e| e.trashed.as_deref() == Some(location)) {
+                entry.trashed = None;
+            }
+            Ok(())
+        })
+    }
+
+    /// Delete registered files whose ttl has passed, but only if their content is unchanged
+    pub fn collect_garbage(&self, now: DateTime<Local>, dry_run: bool) -> Result<GcReport> {
+        self.update(|entries| {
+            let (kept, report) = Self::collect(entries.clone(), now, dry_run)?;
+            if !dry_run {
+                *entries = kept;
+            }
+            Ok(report)
+        })
+    }
+
+    /// The entries `collect_garbage` keeps, and what it did with the rest
+    fn collect(entries: Vec<Entry>, now: DateTime<Local>, dry_run: bool) -> Result<(Vec<Entry>, GcReport)> {
+        let mut report = GcReport::default();
+        let mut kept = Vec::new();
+
+        for entry in entries {
+            if entry.trashed.is_some() || entry.expires.is_none_or(|expires| expires > now) {
+                kept.push(entry);
+            } else if !entry.path.exists() {
+                report.missing.push(entry.path);
+            } else if hash_file(&entry.path)? != entry.hash {
+                info!(
+                    "Expired file modified since creation, keeping: {path:?}",
+                    path = entry.path
+                );
+                report.modified.push(entry.path.clone());
+                kept.push(entry);
+            } else {
+                if !dry_run {
+                    info!("Deleting expired file: {path:?}", path = entry.path);
+                    fs::remove_file(&entry.path)
+                        .with_context(|| format!("Failed to delete expired file: {:?}", entry.path))?;
+                }
+                report.deleted.push(entry.path);
+            }
+        }
+
+        Ok((kept, report))
+    }
+}
+
+pub fn absolute(path: &Path) -> Result<PathBuf> {
+    std::path::absolute(path).with_context(|| format!("Failed to resolve absolute path: {path:?}"))
+}
+
+pub fn hash_content(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+pub fn hash_file(path: &Path) -> Result<String> {
+    let content = fs::read(path).with_context(|| format!("Failed to read file for hashing: {path:?}"))?;
+    Ok(hash_content(&content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn entry(path: &Path, kind: &str) -> Entry {
+        Entry {
+            path: path.to_path_buf(),
+            kind: kind.to_string(),
+            created: Local::now(),
+            expires: None,
+            hash: hash_content(b"content"),
+            cwd: PathBuf::from("/"),
+            dirs: Vec::new(),
+            trashed: None,
+        }
+    }
+
+    #[test]
+    fn test_load_missing_registry_is_empty() {
+        let tempdir = tempdir().unwrap();
+        let registry = Registry::new(tempdir.path().join("registry.yml"));
+
+        assert!(registry.load().unwrap().is_empty());
+        assert!(registry.last().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_record_find_and_forget() {
+        let tempdir = tempdir().unwrap();
+        let registry = Registry::new(tempdir.path().join("nested/registry.yml"));
+        let first = tempdir.path().join("first.py");
+        let second = tempdir.path().join("second.sh");
+
+        registry.record(entry(&first, "py")).unwrap();
+        registry.record(entry(&second, "sh")).unwrap();
+        // Re-recording the same path replaces the old entry and moves it to the end
+        registry.record(entry(&first, "py3")).unwrap();
+
+        let entries = registry.load().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(registry.last().unwrap().unwrap().kind, "py3");
+        let last_sh = registry.last_matching(|e| e.kind == "sh").unwrap().unwrap();
+        assert_eq!(last_sh.path, second);
+        assert!(registry.last_matching(|e| e.kind == "py").unwrap().is_none());
+        assert_eq!(registry.find(&second).unwrap().unwrap().kind, "sh");
+
+        let forgotten = registry.forget(&second).unwrap().unwrap();
+        assert_eq!(forgotten.path, second);
+        assert!(registry.find(&second).unwrap().is_none());
+        assert!(registry.forget(&second).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_trashed_entries() {
+        let tempdir = tempdir().unwrap();
+        let registry = Registry::new(tempdir.path().join("registry.yml"));
+        let path = tempdir.path().join("notes.md");
+        let location = tempdir.path().join("Trash/files/notes.md");
+
+        registry.record(entry(&path, "md")).unwrap();
+        registry.mark_trashed(&path, &location).unwrap();
+
+        assert!(registry.find(&path).unwrap().is_none());
+        assert!(registry.last().unwrap().is_none());
+        assert_eq!(
+            registry.find_trashed(None).unwrap().unwrap().trashed,
+            Some(location.clone())
+        );
+
+        // Creating a new file at the same path keeps the trashed entry restorable
+        registry.record(entry(&path, "md")).unwrap();
+        assert!(registry.find(&path).unwrap().is_some());
+        assert!(registry.find_trashed(Some(&path)).unwrap().is_some());
+
+        registry.forget(&path).unwrap();
+        registry.mark_restored(&location).unwrap();
+        assert!(registry.find_trashed(None).unwrap().is_none());
+        assert_eq!(registry.find(&path).unwrap().unwrap().kind, "md");
+    }
+
+    #[test]
+    fn test_concurrent_records_are_all_kept() {
+        let tempdir = tempdir().unwrap();
+        let path = tempdir.path().join("registry.yml");
+
+        std::thread::scope(|scope| {
+            for thread in 0..8 {
+                let registry = Registry::new(path.clone());
+                let dir = tempdir.path();
+                scope.spawn(move || {
+                    for index in 0..10 {
+                        let file = dir.join(format!("{thread}-{index}.txt"));
+                        registry.record(entry(&file, "txt")).unwrap();
+                    }
+                });
+            }
+        });
+
+        let registry = Registry::new(path);
+        assert_eq!(registry.load().unwrap().len(), 80);
+        // Only the registry and its lock are left behind, no half-written copies
+        let mut files: Vec<String> = fs::read_dir(tempdir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["registry.yml", "registry.yml.lock"]);
+    }
+
+    #[test]
+    fn test_hash_content_is_stable() {
+        assert_eq!(
+            hash_content(b"hello"),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_ne!(hash_content(b"hello"), hash_content(b"hello\n"));
+    }
+
+    #[test]
+    fn test_collect_garbage() {
+        let tempdir = tempdir().unwrap();
+        let registry = Registry::new(tempdir.path().join("registry.yml"));
+        let now = Local::now();
+        let past = now - chrono::Duration::hours(1);
+        let future = now + chrono::Duration::hours(1);
+
+        let expired = tempdir.path().join("expired.txt");
+        let edited = tempdir.path().join("edited.txt");
+        let fresh = tempdir.path().join("fresh.txt");
+        let forever = tempdir.path().join("forever.txt");
+        let gone = tempdir.path().join("gone.txt");
+        for path in [&expired, &edited, &fresh, &forever] {
+            fs::write(path, "content").unwrap();
+        }
+
+        for (path, expires) in [
+            (&expired, Some(past)),
+            (&edited, Some(past)),
+            (&fresh, Some(future)),
+            (&forever, None),
+            (&gone, Some(past)),
+        ] {
+            registry
+                .record(Entry {
+                    expires,
+                    ..entry(path, "txt")
+                })
+                .unwrap();
+        }
+        fs::write(&edited, "real work").unwrap();
+
+        let report = registry.collect_garbage(now, true).unwrap();
+        assert_eq!(report.deleted, vec![expired.clone()]);
+        assert!(expired.exists());
+        assert_eq!(registry.load().unwrap().len(), 5);
+
+        let report = registry.collect_garbage(now, false).unwrap();
+        assert_eq!(report.deleted, vec![expired.clone()]);
+        assert_eq!(report.modified, vec![edited.clone()]);
+        assert_eq!(report.missing, vec![gone.clone()]);
+        assert!(!expired.exists());
+        assert!(edited.exists());
+
+        let remaining: Vec<PathBuf> = registry.load().unwrap().into_iter().map(|e| e.path).collect();
+        assert_eq!(remaining, vec![edited, fresh, forever]);
+    }
+}
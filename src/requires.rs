@@ -0,0 +1,153 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use std::path::Path;
+
+/// Conditions that must hold before a kind can be created
+///
+/// Every condition accepts a single value or a list, and all of them must be met.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Requires {
+    /// The target directory must be inside a git work tree
+    #[serde(default)]
+    pub in_git_repo: bool,
+    /// Files that must exist; relative paths are looked up in the target directory and its ancestors
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub file_exists: Vec<String>,
+    /// Executables that must be on PATH
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub command: Vec<String>,
+    /// Environment variables that must be set and non-empty
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub env: Vec<String>,
+}
+
+impl Requires {
+    /// Describe every unmet condition for a file created in `dir`
+    pub fn unmet(&self, dir: &Path) -> Vec<String> {
+        let mut unmet = Vec::new();
+
+        if self.in_git_repo && find_upwards(dir, ".git").is_none() {
+            unmet.push(format!("{dir:?} is not inside a git repository"));
+        }
+
+        for file in &self.file_exists {
+            let found = if Path::new(file).is_absolute() {
+                Path::new(file).exists()
+            } else {
+                find_upwards(dir, file).is_some()
+            };
+            if !found {
+                unmet.push(format!("{file} not found in {dir:?} or any parent directory"));
+            }
+        }
+
+        for command in &self.command {
+            if !on_path(command) {
+                unmet.push(format!("command `{command}` not found on PATH"));
+            }
+        }
+
+        for var in &self.env {
+            if std::env::var(var).map_or(true, |value| value.is_empty()) {
+                unmet.push(format!("environment variable ${var} is not set"));
+            }
+        }
+
+        unmet
+    }
+}
+
+fn find_upwards(dir: &Path, name: &str) -> Option<std::path::PathBuf> {
+    dir.ancestors()
+        .map(|ancestor| ancestor.join(name))
+        .find(|path| path.exists())
+}
+
+fn on_path(command: &str) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    let is_executable = |path: &Path| {
+        path.metadata()
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+    };
+
+    if command.contains('/') {
+        return is_executable(Path::new(command));
+    }
+
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| is_executable(&dir.join(command))))
+}
+
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_deserialize_one_or_many() {
+        let requires: Requires =
+            serde_yaml::from_str("in_git_repo: true\nfile_exists: Cargo.toml\ncommand: [sh, cargo]").unwrap();
+
+        assert!(requires.in_git_repo);
+        assert_eq!(requires.file_exists, vec!["Cargo.toml"]);
+        assert_eq!(requires.command, vec!["sh", "cargo"]);
+        assert!(requires.env.is_empty());
+
+        assert!(serde_yaml::from_str::<Requires>("in_repo: true").is_err());
+    }
+
+    #[test]
+    fn test_unmet() {
+        let tempdir = tempdir().unwrap();
+        let nested = tempdir.path().join("crate/src");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(tempdir.path().join("crate/Cargo.toml"), "").unwrap();
+
+        let met = Requires {
+            file_exists: vec!["Cargo.toml".to_string()],
+            command: vec!["sh".to_string()],
+            env: vec!["PATH".to_string()],
+            ..Default::default()
+        };
+        assert!(met.unmet(&nested).is_empty());
+
+        let unmet = Requires {
+            in_git_repo: true,
+            file_exists: vec!["pyproject.toml".to_string()],
+            command: vec!["definitely-not-a-real-command".to_string()],
+            env: vec!["TMP_TEST_UNSET_VARIABLE".to_string()],
+        };
+        let reasons = unmet.unmet(&nested);
+        assert_eq!(reasons.len(), 4);
+        assert!(reasons[0].contains("not inside a git repository"));
+        assert!(reasons[1].contains("pyproject.toml not found"));
+        assert!(reasons[2].contains("definitely-not-a-real-command"));
+        assert!(reasons[3].contains("$TMP_TEST_UNSET_VARIABLE"));
+
+        fs::create_dir(tempdir.path().join(".git")).unwrap();
+        let in_repo = Requires {
+            in_git_repo: true,
+            ..Default::default()
+        };
+        assert!(in_repo.unmet(&nested).is_empty());
+    }
+}
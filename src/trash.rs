@@ -0,0 +1,178 @@
+use crate::error::{Context, Result, TmpError};
+use chrono::Local;
+use log::{debug, info};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// A freedesktop.org (XDG) trash directory
+#[derive(Debug, Clone)]
+pub struct Trash {
+    root: PathBuf,
+}
+
+impl Trash {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// The user's home trash: `$XDG_DATA_HOME/Trash`, falling back to `~/.local/share/Trash`
+    pub fn home() -> Result<Self> {
+        let data_home = match std::env::var("XDG_DATA_HOME") {
+            Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => {
+                let home = std::env::var("HOME").map_err(|_| TmpError::HomeNotSet)?;
+                Path::new(&home).join(".local/share")
+            }
+        };
+        Ok(Self::new(data_home.join("Trash")))
+    }
+
+    /// Move `path` into the trash, returning its location inside the trash
+    pub fn put(&self, path: &Path) -> Result<PathBuf> {
+        let path = std::path::absolute(path).with_context(|| format!("Failed to resolve absolute path: {path:?}"))?;
+        let files_dir = self.root.join("files");
+        let info_dir = self.root.join("info");
+        fs::create_dir_all(&files_dir).with_context(|| format!("Failed to create trash directory: {files_dir:?}"))?;
+        fs::create_dir_all(&info_dir).with_context(|| format!("Failed to create trash directory: {info_dir:?}"))?;
+
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| TmpError::NotTrashable(path.clone()))?
+            .to_string_lossy()
+            .to_string();
+
+        // Reserve a unique name by creating the .trashinfo file exclusively, as the spec requires
+        let mut counter = 1;
+        let (name, mut info_file) = loop {
+            let name = if counter == 1 {
+                file_name.clone()
+            } else {
+                format!("{file_name}.{counter}")
+            };
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(info_dir.join(format!("{name}.trashinfo")))
+            {
+                Ok(file) => break (name, file),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => counter += 1,
+                Err(e) => return Err(e).context("Failed to create trash info file"),
+            }
+        };
+
+        let info = format!(
+            "[Trash Info]\nPath={path}\nDeletionDate={date}\n",
+            path = percent_encode(&path.to_string_lossy()),
+            date = Local::now().format("%Y-%m-%dT%H:%M:%S")
+        );
+        std::io::Write::write_all(&mut info_file, info.as_bytes()).context("Failed to write trash info file")?;
+
+        let location = files_dir.join(&name);
+        move_file(&path, &location)?;
+
+        info!("Moved {path:?} to trash: {location:?}");
+        Ok(location)
+    }
+
+    /// Move a trashed file back to `original`, refusing to overwrite anything there
+    pub fn restore(&self, location: &Path, original: &Path) -> Result<()> {
+        if !location.exists() {
+            return Err(TmpError::NotInTrash(location.to_path_buf()));
+        }
+        if original.exists() {
+            return Err(TmpError::TargetExists(original.to_path_buf()));
+        }
+        if let Some(parent) = original.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to recreate directory: {parent:?}"))?;
+        }
+
+        move_file(location, original)?;
+
+        if let Some(name) = location.file_name() {
+            let info_file = self
+                .root
+                .join("info")
+                .join(format!("{name}.trashinfo", name = name.to_string_lossy()));
+            debug!("Removing trash info file: {info_file:?}");
+            fs::remove_file(&info_file)
+                .or_else(|e| {
+                    if e.kind() == ErrorKind::NotFound {
+                        Ok(())
+                    } else {
+                        Err(e)
+                    }
+                })
+                .with_context(|| format!("Failed to remove trash info file: {info_file:?}"))?;
+        }
+
+        info!("Restored {location:?} to {original:?}");
+        Ok(())
+    }
+}
+
+/// Rename, falling back to copy and delete when crossing filesystems
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    debug!("Rename failed, copying {from:?} to {to:?}");
+    fs::copy(from, to).with_context(|| format!("Failed to move {from:?} to {to:?}"))?;
+    fs::remove_file(from).with_context(|| format!("Failed to remove {from:?} after copying"))?;
+    Ok(())
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_put_and_restore() {
+        let tempdir = tempdir().unwrap();
+        let trash = Trash::new(tempdir.path().join("Trash"));
+        let file = tempdir.path().join("my file.txt");
+
+        fs::write(&file, "first").unwrap();
+        let first = trash.put(&file).unwrap();
+        assert!(!file.exists());
+        assert_eq!(fs::read_to_string(&first).unwrap(), "first");
+
+        let info = fs::read_to_string(tempdir.path().join("Trash/info/my file.txt.trashinfo")).unwrap();
+        assert!(info.starts_with("[Trash Info]\n"));
+        assert!(info.contains("my%20file.txt"));
+
+        // A second file with the same name gets a unique trash name
+        fs::write(&file, "second").unwrap();
+        let second = trash.put(&file).unwrap();
+        assert_ne!(first, second);
+
+        trash.restore(&first, &file).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "first");
+        assert!(!tempdir.path().join("Trash/info/my file.txt.trashinfo").exists());
+
+        // Restoring over an existing file is refused
+        assert!(matches!(trash.restore(&second, &file), Err(TmpError::TargetExists(_))));
+        assert!(second.exists());
+
+        fs::remove_file(&file).unwrap();
+        assert!(matches!(trash.restore(&first, &file), Err(TmpError::NotInTrash(_))));
+        assert!(matches!(trash.put(Path::new("/")), Err(TmpError::NotTrashable(_))));
+    }
+
+    #[test]
+    fn test_percent_encode() {
+        assert_eq!(percent_encode("/tmp/a b/ü.txt"), "/tmp/a%20b/%C3%BC.txt");
+    }
+}
@@ -0,0 +1,127 @@
+use crate::config::data_dir;
+use crate::error::{Context, Result};
+use crate::registry::{absolute, hash_file};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A project config the user reviewed, pinned to the content they reviewed
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+struct Trusted {
+    path: PathBuf,
+    hash: String,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct TrustFile {
+    configs: Vec<Trusted>,
+}
+
+/// Project configs allowed to run commands, like direnv's `allow`
+///
+/// A project config comes with whatever repo it's in, so its `post_create` hooks and
+/// `requires.command` are refused until the user trusts it. Editing it revokes the trust.
+#[derive(Debug, Clone)]
+pub struct Trust {
+    path: PathBuf,
+}
+
+impl Trust {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// The user's trusted configs, in `~/.local/share/tmp/trusted.yml`
+    pub fn home() -> Result<Self> {
+        Ok(Self::new(data_dir()?.join("trusted.yml")))
+    }
+
+    /// Whether `config` was trusted with exactly the content it has now
+    pub fn is_trusted(&self, config: &Path) -> bool {
+        let (Ok(path), Ok(hash)) = (absolute(config), hash_file(config)) else {
+            return false;
+        };
+        match self.load() {
+            Ok(configs) => configs.contains(&Trusted { path, hash }),
+            Err(e) => {
+                debug!("Treating {config:?} as untrusted: {e}");
+                false
+            }
+        }
+    }
+
+    /// Trust `config` as it is now
+    pub fn add(&self, config: &Path) -> Result<()> {
+        let path = absolute(config)?;
+        let hash = hash_file(config)?;
+        let mut configs = self.load()?;
+        configs.retain(|trusted| trusted.path != path);
+        info!("Trusting {path:?}");
+        configs.push(Trusted { path, hash });
+        self.save(configs)
+    }
+
+    /// Stop trusting `config`, returning whether it was trusted
+    pub fn revoke(&self, config: &Path) -> Result<bool> {
+        let path = absolute(config)?;
+        let mut configs = self.load()?;
+        let before = configs.len();
+        configs.retain(|trusted| trusted.path != path);
+        if configs.len() == before {
+            return Ok(false);
+        }
+        info!("No longer trusting {path:?}");
+        self.save(configs)?;
+        Ok(true)
+    }
+
+    fn load(&self) -> Result<Vec<Trusted>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let content = fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read trusted configs: {:?}", self.path))?;
+        let file: TrustFile = serde_yaml::from_str(&content)
+            .with_context(|| format!("Failed to parse trusted configs: {:?}", self.path))?;
+        Ok(file.configs)
+    }
+
+    fn save(&self, configs: Vec<Trusted>) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to create data directory: {parent:?}"))?;
+        }
+
+        let content = serde_yaml::to_string(&TrustFile { configs }).context("Failed to serialize trusted configs")?;
+        fs::write(&self.path, content).with_context(|| format!("Failed to write trusted configs: {:?}", self.path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_trust_is_pinned_to_content() {
+        let tempdir = tempdir().unwrap();
+        let trust = Trust::new(tempdir.path().join("trusted.yml"));
+        let config = tempdir.path().join(".tmp.yml");
+        fs::write(&config, "kinds: {}\n").unwrap();
+
+        assert!(!trust.is_trusted(&config));
+        trust.add(&config).unwrap();
+        assert!(trust.is_trusted(&config));
+
+        // Any edit needs a fresh review
+        fs::write(&config, "kinds: {}\n# changed\n").unwrap();
+        assert!(!trust.is_trusted(&config));
+        trust.add(&config).unwrap();
+        assert!(trust.is_trusted(&config));
+
+        assert!(trust.revoke(&config).unwrap());
+        assert!(!trust.is_trusted(&config));
+        assert!(!trust.revoke(&config).unwrap());
+    }
+}
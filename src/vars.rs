@@ -0,0 +1,378 @@
+use chrono::Local;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Named values substituted for `{tmp.name}` placeholders
+///
+/// Hooks, `dir` and `default_name` also accept the short `{name}`; file content only takes the
+/// long form, so the file's own braces such as `${dir}` or `f"{name}"` are left alone. A doubled
+/// opening brace keeps a placeholder as it is: `{{tmp.name}` renders as `{tmp.name}`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Vars(BTreeMap<String, String>);
+
+/// Variables that describe the file being created, which a kind's variables alone don't have
+pub const FILE_KEYS: &[&str] = &["path", "name", "stem", "dir"];
+
+/// A value `Vars::templatize` turned back into its placeholder
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replacement {
+    /// 1-based line of the value in the original text
+    pub line: usize,
+    pub key: String,
+    pub value: String,
+}
+
+impl Vars {
+    /// Built-in variables describing a kind: `{kind}`, `{suffix}`, `{date}` (2024-01-31) and `{time}` (23:59:59)
+    pub fn for_kind(kind: &str, suffix: &str) -> Self {
+        let now = Local::now();
+
+        let mut vars = Self::default();
+        vars.set("suffix", suffix);
+        vars.set("kind", kind);
+        vars.set("date", now.format("%Y-%m-%d").to_string());
+        vars.set("time", now.format("%H:%M:%S").to_string());
+        vars
+    }
+
+    /// The kind variables plus ones describing a file at `path`
+    ///
+    /// `{path}` absolute path, `{name}` file name, `{stem}` file name without suffix and `{dir}` parent directory.
+    pub fn for_file(kind: &str, suffix: &str, path: &Path) -> Self {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let stem = match name.strip_suffix(&format!(".{suffix}")) {
+            Some(stem) if !suffix.is_empty() => stem.to_string(),
+            _ => name.clone(),
+        };
+        let dir = path
+            .parent()
+            .map(|dir| dir.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let mut vars = Self::for_kind(kind, suffix);
+        vars.set("path", path.to_string_lossy());
+        vars.set("name", name);
+        vars.set("stem", stem);
+        vars.set("dir", dir);
+        vars
+    }
+
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.0.insert(key.into(), value.into());
+    }
+
+    /// Add every variable from `other`, overriding ones with the same name
+    pub fn extend(&mut self, other: &Vars) {
+        self.0.extend(other.0.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Replace every `{key}` and `{tmp.key}` in `text` with its value, leaving unknown placeholders alone
+    pub fn render(&self, text: &str) -> String {
+        self.render_with(text, Cow::Borrowed)
+    }
+
+    /// Like `render`, but each value is passed through `transform` first, e.g. to quote it
+    pub fn render_with<'a>(&'a self, text: &str, transform: impl Fn(&'a str) -> Cow<'a, str>) -> String {
+        self.substitute(text, true, transform)
+    }
+
+    /// Replace every `{tmp.key}` in a kind's content, leaving the short `{key}` alone
+    pub fn render_content(&self, text: &str) -> String {
+        self.substitute(text, false, Cow::Borrowed)
+    }
+
+    /// A single pass over `text`, so placeholders inside inserted values are never expanded
+    fn substitute<'a>(&'a self, text: &str, short: bool, transform: impl Fn(&'a str) -> Cow<'a, str>) -> String {
+        let mut rendered = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            let escaped = after.starts_with('{');
+            let placeholder = if escaped { &after[1..] } else { after };
+            let end = placeholder.find('}');
+            let value = end.and_then(|end| self.lookup(&placeholder[..end], short));
+            match (end, value) {
+                // `{{tmp.` always escapes, so text copied into content comes out as it went in
+                (Some(end), _) if escaped && (value.is_some() || placeholder.starts_with("tmp.")) => {
+                    rendered.push('{');
+                    rendered.push_str(&placeholder[..=end]);
+                    rest = &placeholder[end + 1..];
+                }
+                (Some(end), Some(value)) => {
+                    rendered.push_str(&transform(value));
+                    rest = &placeholder[end + 1..];
+                }
+                _ => {
+                    rendered.push('{');
+                    rest = after;
+                }
+            }
+        }
+        rendered.push_str(rest);
+        rendered
+    }
+
+    /// Keys of the `{tmp.KEY}` placeholders in content that `render_content` would leave as they are
+    ///
+    /// Escaped placeholders such as `{{tmp.KEY}` are meant to stay and aren't listed.
+    pub fn undefined<'t>(&self, text: &'t str) -> Vec<&'t str> {
+        let mut undefined = Vec::new();
+        let mut rest = text;
+        // The same steps as `substitute`, so escapes are read the way rendering reads them
+        while let Some(start) = rest.find('{') {
+            let after = &rest[start + 1..];
+            rest = after;
+            let escaped = after.starts_with('{');
+            let placeholder = if escaped { &after[1..] } else { after };
+            let Some(end) = placeholder.find('}') else {
+                continue;
+            };
+            let name = &placeholder[..end];
+            if escaped {
+                if name.starts_with("tmp.") || self.lookup(name, false).is_some() {
+                    rest = &placeholder[end + 1..];
+                }
+                continue;
+            }
+            if let Some(key) = name.strip_prefix("tmp.")
+                && !key.is_empty()
+                && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                && !self.0.contains_key(key)
+                && !undefined.contains(&key)
+            {
+                undefined.push(key);
+            }
+        }
+        undefined
+    }
+
+    fn lookup(&self, placeholder: &str, short: bool) -> Option<&str> {
+        let key = match placeholder.strip_prefix("tmp.") {
+            Some(key) => key,
+            None if short => placeholder,
+            None => return None,
+        };
+        self.0.get(key).map(String::as_str)
+    }
+
+    /// The reverse of `render_content`: replace each value in `text` with its `{tmp.key}` placeholder
+    ///
+    /// Longer values are replaced first and only as whole words, so a stem of `io` leaves `ratio`
+    /// alone. Returns the text and each replacement made, in the order they appear.
+    pub fn templatize(&self, text: &str) -> (String, Vec<Replacement>) {
+        let mut vars: Vec<(&String, &String)> = self.0.iter().filter(|(_, value)| !value.is_empty()).collect();
+        vars.sort_by_key(|(_, value)| std::cmp::Reverse(value.len()));
+
+        // Literal text and placeholders already put in, which later values must not match inside,
+        // each with its offset in `text`
+        let mut segments = vec![(text.to_string(), false, 0)];
+        let mut replacements = Vec::new();
+        for (key, value) in vars {
+            let mut next = Vec::new();
+            for (segment, placeholder, offset) in segments {
+                if placeholder {
+                    next.push((segment, true, offset));
+                    continue;
+                }
+                let mut last = 0;
+                for (index, _) in segment.match_indices(value.as_str()) {
+                    let end = index + value.len();
+                    if index < last || !is_word_boundary(&segment, index, end) {
+                        continue;
+                    }
+                    next.push((segment[last..index].to_string(), false, offset + last));
+                    next.push((format!("{{tmp.{key}}}"), true, offset + index));
+                    replacements.push((offset + index, key, value));
+                    last = end;
+                }
+                next.push((segment[last..].to_string(), false, offset + last));
+            }
+            segments = next;
+        }
+
+        replacements.sort_by_key(|(offset, _, _)| *offset);
+        let replacements = replacements
+            .into_iter()
+            .map(|(offset, key, value)| Replacement {
+                line: text[..offset].matches('\n').count() + 1,
+                key: key.clone(),
+                value: value.clone(),
+            })
+            .collect();
+        let text = segments.into_iter().map(|(segment, _, _)| segment).collect();
+        (text, replacements)
+    }
+}
+
+fn is_word_boundary(text: &str, start: usize, end: usize) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    !text[..start].chars().next_back().is_some_and(is_word) && !text[end..].chars().next().is_some_and(is_word)
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for Vars {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self(iter.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+    }
+}
+
+/// The first `{name}` or `{tmp.name}` placeholder left in `text`, ignoring shell-style `${NAME}`
+pub fn unresolved(text: &str) -> Option<&str> {
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let after = &rest[start + 1..];
+        let escaped = rest[..start].ends_with('$');
+        if let Some(end) = after.find('}') {
+            let name = &after[..end];
+            let is_name = !name.is_empty()
+                && name
+                    .strip_prefix("tmp.")
+                    .unwrap_or(name)
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if is_name && !escaped {
+                return Some(name);
+            }
+        }
+        rest = after;
+    }
+    None
+}
+
+/// Parse a `KEY=VALUE` command line argument
+pub fn parse_key_value(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got '{arg}'")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_file() {
+        let vars = Vars::for_file("py", "py", Path::new("/home/me/scripts/fetch.py"));
+
+        assert_eq!(vars.render("{path}"), "/home/me/scripts/fetch.py");
+        assert_eq!(vars.render("{name}"), "fetch.py");
+        assert_eq!(vars.render("{stem}"), "fetch");
+        assert_eq!(vars.render("{dir}"), "/home/me/scripts");
+        assert_eq!(vars.render("{kind}"), "py");
+        assert_eq!(vars.render("{date}").len(), 10);
+
+        let bare = Vars::for_file("make", "", Path::new("/src/Makefile"));
+        assert_eq!(bare.render("{stem}"), "Makefile");
+    }
+
+    #[test]
+    fn test_render() {
+        let mut vars = Vars::default();
+        vars.set("path", "/tmp/a b.sh");
+        vars.set("kind", "sh");
+
+        assert_eq!(vars.render("file: {path}"), "file: /tmp/a b.sh");
+        assert_eq!(vars.render("{kind}: {unknown}"), "sh: {unknown}");
+
+        vars.extend(&Vars::from_iter([("kind", "bash")]));
+        assert_eq!(vars.render("{kind}"), "bash");
+
+        // Inserted values are never rendered again
+        vars.set("stdin", "{kind} {path");
+        assert_eq!(vars.render("{ {stdin}}"), "{ {kind} {path}");
+        assert_eq!(
+            vars.render("{tmp.kind} {{kind} {{tmp.kind} {{x}}"),
+            "bash {kind} {tmp.kind} {{x}}"
+        );
+    }
+
+    #[test]
+    fn test_render_content() {
+        let vars = Vars::from_iter([("dir", "/tmp"), ("name", "x.py"), ("stdin", "{tmp.dir} {cursor}")]);
+
+        assert_eq!(vars.render_content("cd ${dir} # {tmp.dir}"), "cd ${dir} # /tmp");
+        assert_eq!(
+            vars.render_content("f\"hello {name}\" {{tmp.name}"),
+            "f\"hello {name}\" {tmp.name}"
+        );
+        assert_eq!(vars.render_content("{tmp.stdin}"), "{tmp.dir} {cursor}");
+        assert_eq!(vars.render_content("{{tmp.unknown} {{x}}"), "{tmp.unknown} {{x}}");
+    }
+
+    #[test]
+    fn test_undefined() {
+        let vars = Vars::from_iter([("name", "x.py")]);
+        assert_eq!(
+            vars.undefined("{tmp.name} {tmp.author} {name} {tmp.author} {tmp.year}"),
+            vec!["author", "year"]
+        );
+        // Escaped placeholders stay on purpose
+        assert!(vars.undefined("{{tmp.author} {{{tmp.author} ${tmp.x y}").is_empty());
+    }
+
+    #[test]
+    fn test_templatize() {
+        let vars = Vars::from_iter([
+            ("stem", "fetch"),
+            ("author", "Ada Lovelace"),
+            ("date", "2024-01-31"),
+            ("short", "io"),
+        ]);
+        let text = "\"\"\"fetch: by Ada Lovelace, 2024-01-31\"\"\"\nratio = fetch_all(io)\n";
+
+        let (templated, replacements) = vars.templatize(text);
+        assert_eq!(
+            templated,
+            "\"\"\"{tmp.stem}: by {tmp.author}, {tmp.date}\"\"\"\nratio = fetch_all({tmp.short})\n"
+        );
+        let found: Vec<(usize, &str, &str)> = replacements
+            .iter()
+            .map(|r| (r.line, r.key.as_str(), r.value.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (1, "stem", "fetch"),
+                (1, "author", "Ada Lovelace"),
+                (1, "date", "2024-01-31"),
+                (2, "short", "io")
+            ]
+        );
+
+        let mut render = vars.clone();
+        render.set("kind", "unused");
+        assert_eq!(render.render_content(&templated), text);
+
+        // A value never matches inside a placeholder put in for another one
+        let (templated, _) = Vars::from_iter([("date", "2024"), ("year", "date")]).templatize("2024 date");
+        assert_eq!(templated, "{tmp.date} {tmp.year}");
+    }
+
+    #[test]
+    fn test_unresolved() {
+        assert_eq!(unresolved("~/tmp/{project}/x"), Some("project"));
+        assert_eq!(unresolved("${HOME}/{} {a b}"), None);
+        assert_eq!(unresolved("tests/py"), None);
+        assert_eq!(unresolved("~/{tmp.project}"), Some("tmp.project"));
+    }
+
+    #[test]
+    fn test_parse_key_value() {
+        assert_eq!(
+            parse_key_value("author=Ada"),
+            Ok(("author".to_string(), "Ada".to_string()))
+        );
+        assert_eq!(parse_key_value("url=a=b"), Ok(("url".to_string(), "a=b".to_string())));
+        assert!(parse_key_value("novalue").is_err());
+        assert!(parse_key_value("=x").is_err());
+    }
+}
@@ -0,0 +1,35 @@
+use std::process::{Command, Output};
+use tempfile::tempdir;
+
+/// Run the tmp binary in `dir` with HOME unset
+fn tmp_without_home(dir: &std::path::Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_tmp"))
+        .args(args)
+        .current_dir(dir)
+        .env_remove("HOME")
+        .env_remove("VISUAL")
+        .env_remove("EDITOR")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_runs_without_home() {
+    let tempdir = tempdir().unwrap();
+
+    // Commands that don't need the history still work
+    for args in [&["list"][..], &["man"], &["completions", "bash"]] {
+        let output = tmp_without_home(tempdir.path(), args);
+        assert!(output.status.success(), "{args:?}: {output:?}");
+    }
+
+    // Files are created from the built-in kinds, just not recorded
+    let output = tmp_without_home(tempdir.path(), &["sh", "x"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(tempdir.path().join("x.sh").is_file());
+
+    // The history itself can't be found
+    let output = tmp_without_home(tempdir.path(), &["history"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("HOME environment variable not set"));
+}